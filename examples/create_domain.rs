@@ -78,7 +78,8 @@ fn logout(client: &mut Client) -> Result<(), Error> {
 }
 
 fn create(client: &mut Client) -> Result<(), Error> {
-    client.call_and_check("set-session", json!({"description": "R80 Multi-Domain Lab"}))?;
+    // The changes are discarded if an error is returned before the session is committed
+    let mut session = client.session("R80 Multi-Domain Lab")?;
 
     let add = json!({
         "name": "API_Domain",
@@ -92,7 +93,7 @@ fn create(client: &mut Client) -> Result<(), Error> {
 
     println!("\nCreating API_Domain with IP 10.1.1.113\n");
 
-    client.call_and_check("add-domain", add)?;
+    session.call_and_check("add-domain", add)?;
 
     let any = json!({
         "name": "AnyHost",
@@ -103,8 +104,8 @@ fn create(client: &mut Client) -> Result<(), Error> {
         }
     });
 
-    client.call_and_check("set-trusted-client", any)?;
-    client.call_and_check("publish", json!({}))?;
+    session.call_and_check("set-trusted-client", any)?;
+    session.commit()?;
 
     Ok(())
 }
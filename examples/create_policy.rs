@@ -15,7 +15,6 @@ fn main() {
 
     if let Err(e) = run(&mut client) {
         eprintln!("Error: {}", e);
-        client.call("discard", json!({})).expect("Failed to discard changes");
         enter_to_exit();
         process::exit(1);
     }
@@ -27,19 +26,22 @@ fn main() {
 fn run(client: &mut Client) -> Result<(), Error> {
     login(client)?;
 
-    client.call_and_check("set-session", json!({"description": "R80 Multi-Domain Lab"}))?;
+    // The changes are discarded if an error is returned before the session is committed
+    let mut session = client.session("R80 Multi-Domain Lab")?;
 
-    gw(client)?;
+    gw(&mut session)?;
 
     println!("Publishing");
-    client.call_and_check("publish", json!({}))?;
+    session.commit()?;
 
-    objects(client)?;
-    policy(client)?;
-    layers(client)?;
+    let mut session = client.session("R80 Multi-Domain Lab")?;
+
+    objects(&mut session)?;
+    policy(&mut session)?;
+    layers(&mut session)?;
 
     println!("Publishing");
-    client.call_and_check("publish", json!({}))?;
+    session.commit()?;
 
     install(client)?;
 
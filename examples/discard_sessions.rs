@@ -1,8 +1,6 @@
 // cargo run --example discard_sessions
 
-use cp_api::{Client, Error};
-use serde_json::json;
-use rpassword;
+use cp_api::{CleanupPolicy, Client, Error};
 use std::process;
 use std::io::{self, Write};
 
@@ -112,34 +110,36 @@ fn logout(client: &mut Client) -> Result<(), Error> {
 fn discard_sessions(client: &mut Client) -> Result<(), Error> {
     println!("Querying all sessions...");
 
-    let sessions_res = client.query_and_check("show-sessions", json!({"details-level": "full"}))?;
+    // only discard web_api sessions without changes or locks
+    let policy = CleanupPolicy::default();
 
-    for session in sessions_res.objects {
-        // only discard web_api sessions
-        if session["application"] != "WEB_API" {
-            continue;
-        }
-
-        // ignore sessons with changes or locks
-        if session["changes"] != 0 && session["locks"] != 0 {
-            continue;
-        }
-
-        // skip discarding own session until the end
-        if session["uid"] == client.uid() {
-            continue;
+    let report = client.cleanup_sessions(&policy)?;
+    if report.discarded.is_empty() {
+        println!("No sessions to discard");
+    }
+    else {
+        for session in &report.discarded {
+            println!("Session {} from {} will be discarded", session.uid, session.user_name);
         }
 
-        let discard_res = client.call("discard", json!({"uid": session["uid"]}))?;
-        if discard_res.is_success() {
-            println!("Session {} discarded", session["uid"]);
-        }
-        else {
-            println!("Failed to discard session {}", session["uid"]);
+        // discard the sessions listed above, not the sessions there are now
+        let answer = get_input("Discard these sessions? [y/n] ")?;
+        if answer == "y" {
+            for session in &report.discarded {
+                let discard = client.discard_session(session.uid.as_str())?;
+                if discard.is_success() {
+                    println!("Session {} discarded", session.uid);
+                }
+                else {
+                    println!("Failed to discard session {}: {}",
+                             session.uid, discard.data["message"]);
+                }
+            }
         }
     }
 
-    let discard_my_sid = client.call("discard", json!({"uid": client.uid()}))?;
+    let uid = client.uid().to_string();
+    let discard_my_sid = client.discard_session(uid.as_str())?;
     if discard_my_sid.is_success() {
         println!("Discarded my own session");
     }
@@ -2,7 +2,6 @@
 
 use cp_api::{Client, Error};
 use serde_json::json;
-use rpassword;
 use std::process;
 use std::io::{self, Write};
 
@@ -1,7 +1,6 @@
 // cargo run --example show_hosts
 
 use cp_api::{Client, Error};
-use rpassword;
 use std::process;
 use std::io::{self, Write};
 use serde_json::json;
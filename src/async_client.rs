@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Stream};
+use futures_util::compat::Future01CompatExt;
+use reqwest::header::HeaderMap;
+use reqwest::r#async;
+use serde_json::json;
+
+use crate::call_log::CallLog;
+use crate::commands;
+use crate::credentials::Credentials;
+use crate::login_info::LoginInfo;
+use crate::proxy::ProxySettings;
+use crate::rate_limit::RateLimiter;
+use crate::request;
+use crate::response::Response;
+use crate::tls::{self, ClientIdentity};
+use crate::version::ApiVersion;
+use crate::error::{Error, Result};
+
+/// An async Client to communicate with the API.
+///
+/// This is only available with the `async` feature.
+///
+/// It has the same methods as the blocking `Client` for logging in and out,
+/// making calls and queries and waiting for tasks, and writes the same log file.
+///
+/// These are only available with the blocking `Client`:
+///
+/// - certificate fingerprint pinning, `Client::certificate_fingerprint()`
+/// - the known servers file, `Client::known_servers()`
+/// - logout on drop, `Client::logout_on_drop()`, so an AsyncClient must be logged out
+/// - scoped sessions, keepalive and Domains
+/// - retries, streaming queries and automatic login again
+///
+/// The requests are made with the async reqwest client, so the futures must run
+/// on a tokio 0.1 runtime, converted to futures 0.1 with `futures_util::compat`.
+///
+/// ```
+/// use cp_api::AsyncClient;
+/// use serde_json::json;
+///
+/// let mut client = AsyncClient::new("192.168.1.10", 443);
+/// client.certificate("/home/admin/cert.cer");
+/// client.login("user", "pass").await?;
+/// client.call("show-host", json!({"name": "host1"})).await?;
+/// client.logout().await?;
+/// ```
+pub struct AsyncClient {
+    server: String,
+    port: u16,
+    scheme: String,
+    api_path: String,
+    // The pinned API version as written in the URLs.
+    api_version: Option<String>,
+    certificate: String,
+    identity: Option<ClientIdentity>,
+    accept_invalid_certs: bool,
+    proxy: ProxySettings,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+    command_timeouts: HashMap<String, Duration>,
+    session_timeout: u64,
+    domain: String,
+    read_only: bool,
+    mutating_commands: HashMap<String, bool>,
+    continue_last_session: bool,
+    sid: String,
+    uid: String,
+    api_server_version: String,
+    login_info: Option<LoginInfo>,
+    wait_for_task: bool,
+    log: CallLog,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    http_client: Option<r#async::Client>,
+}
+
+impl AsyncClient {
+    /// Create a new AsyncClient to make API calls.
+    /// ```
+    /// let mut client = AsyncClient::new("192.168.1.10", 443);
+    /// ```
+    pub fn new(server: &str, port: u16) -> Self {
+        AsyncClient {
+            server: server.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port,
+            scheme: String::from("https"),
+            api_path: String::from("web_api"),
+            api_version: None,
+            certificate: String::new(),
+            identity: None,
+            accept_invalid_certs: false,
+            proxy: ProxySettings::default(),
+            connect_timeout: Duration::from_secs(30),
+            request_timeout: None,
+            command_timeouts: HashMap::new(),
+            session_timeout: 600,
+            domain: String::new(),
+            read_only: false,
+            mutating_commands: HashMap::new(),
+            continue_last_session: false,
+            sid: String::with_capacity(50),
+            uid: String::with_capacity(40),
+            api_server_version: String::with_capacity(5),
+            login_info: None,
+            wait_for_task: true,
+            log: CallLog::default(),
+            rate_limiter: None,
+            http_client: None,
+        }
+    }
+
+    /// Create a new AsyncClient from the base URL of the API.
+    ///
+    /// See `Client::from_url()`.
+    /// ```
+    /// let mut client = AsyncClient::from_url("https://[fd00::10]:443/web_api")?;
+    /// ```
+    pub fn from_url(base_url: &str) -> Result<Self> {
+        let parts = request::parse_base_url(base_url)?;
+
+        let mut client = AsyncClient::new(parts.server.as_str(), parts.port);
+        client.scheme = parts.scheme;
+        client.api_path = parts.api_path;
+
+        Ok(client)
+    }
+
+    /// Login to the API.
+    ///
+    /// See `Client::login()`.
+    /// ```
+    /// let login = client.login("user", "pass").await?;
+    /// assert!(login.is_success());
+    /// ```
+    pub async fn login(&mut self, user: &str, pass: &str) -> Result<Response> {
+        self.login_with(Credentials::password(user, pass)).await
+    }
+
+    /// Login to the API with an API key.
+    ///
+    /// See `Client::login_with_api_key()`.
+    /// ```
+    /// let login = client.login_with_api_key("eXaMpLeKeY123==").await?;
+    /// assert!(login.is_success());
+    /// ```
+    pub async fn login_with_api_key(&mut self, api_key: &str) -> Result<Response> {
+        self.login_with(Credentials::api_key(api_key)).await
+    }
+
+    // Login with the Credentials and store the session values.
+    async fn login_with(&mut self, credentials: Credentials) -> Result<Response> {
+        if let Credentials::Root = credentials {
+            let msg = String::from("Login as root is only available with the blocking Client");
+            return Err(Error::Custom(msg));
+        }
+
+        let payload = request::login_payload(&credentials,
+                                             self.domain.as_str(),
+                                             self.session_timeout,
+                                             self.read_only,
+                                             self.continue_last_session);
+
+        let login = self.call("login", payload).await?;
+
+        if login.is_success() {
+            let session = request::login_session(&login, self.read_only)?;
+
+            self.sid = session.sid;
+            self.uid = session.uid;
+            self.api_server_version = session.api_server_version;
+            self.login_info = Some(LoginInfo::from_response(&login)?);
+        }
+
+        Ok(login)
+    }
+
+    /// Logout of the API.
+    ///
+    /// See `Client::logout()`.
+    /// ```
+    /// let logout = client.logout().await?;
+    /// assert!(logout.is_success());
+    /// ```
+    pub async fn logout(&mut self) -> Result<Response> {
+        let logout = self.call("logout", json!({})).await?;
+
+        if logout.is_success() {
+            self.sid.clear();
+            self.uid.clear();
+            self.api_server_version.clear();
+            self.login_info = None;
+        }
+
+        Ok(logout)
+    }
+
+    /// Perform an API call.
+    ///
+    /// See `Client::call()`.
+    /// ```
+    /// let payload = json!({"name": "host1", "ip-address": "10.1.1.1"});
+    /// let host = client.call("add-host", payload).await?;
+    /// assert!(host.is_success());
+    /// ```
+    pub async fn call(&mut self, command: &str, payload: serde_json::Value) -> Result<Response> {
+        if self.read_only && self.is_mutating(command) {
+            return Err(Error::ReadOnly(command.to_string()));
+        }
+
+        let (url, headers, mut res) = self.send(command, &payload).await?;
+
+        if self.wait_for_task && res.is_success() && command != "show-task" {
+            match request::task_ids(&res) {
+                Some((true, ids)) => res = self.wait_for_task_id(ids[0].as_str(), command).await?,
+                Some((false, ids)) => res = self.wait_for_tasks(&ids, command).await?,
+                None => (),
+            }
+        }
+
+        self.log.record(command, url.as_str(), &headers, payload, &res)?;
+
+        Ok(res)
+    }
+
+    // Send a Request and return the URL and headers used along with the Response.
+    async fn send(
+        &mut self,
+        command: &str,
+        payload: &serde_json::Value
+        ) -> Result<(String, HeaderMap, Response)>
+    {
+        let url = request::url(self.base_url().as_str(), command);
+        let headers = request::headers(self.sid.as_str())?;
+
+        if let Some(ref limiter) = self.rate_limiter {
+            let delay = tokio_timer::Delay::new(Instant::now() + limiter.reserve());
+            if let Err(e) = delay.compat().await {
+                let msg = format!("Failed to wait for the rate limit: {}", e);
+                return Err(Error::Custom(msg));
+            }
+        }
+
+        let timeout = match self.command_timeouts.get(command) {
+            Some(t) => *t,
+            None => self.request_timeout.unwrap_or(self.connect_timeout),
+        };
+
+        let future = self.http_client()?.post(url.as_str())
+            .headers(headers.clone())
+            .json(payload)
+            .send()
+            .and_then(|reqwest_response| {
+                let status = reqwest_response.status().as_u16();
+                let res_url = reqwest_response.url().to_string();
+                let res_headers = reqwest_response.headers().clone();
+
+                reqwest_response.into_body().concat2()
+                    .map(move |body| (status, res_url, res_headers, body))
+            });
+
+        // The async reqwest client has no read timeout, so the whole request is timed out.
+        let sent = tokio_timer::Timeout::new(future, timeout).compat().await;
+        let (status, res_url, res_headers, body) = match sent {
+            Ok(t) => t,
+            Err(e) => match e.into_inner() {
+                Some(e) => return Err(Error::from(e)),
+                None => {
+                    let msg = format!("'{}' timed out after {} seconds", command,
+                                      timeout.as_secs());
+                    return Err(Error::Custom(msg));
+                }
+            },
+        };
+
+        let res = Response::from_parts(status, res_url.as_str(), &res_headers, &body)?;
+
+        Ok((url, headers, res))
+    }
+
+    // Get the reqwest client, building it if a setting changed since the last call.
+    fn http_client(&mut self) -> Result<r#async::Client> {
+        if let Some(ref client) = self.http_client {
+            return Ok(client.clone());
+        }
+
+        let client = self.build_client()?;
+        self.http_client = Some(client.clone());
+
+        Ok(client)
+    }
+
+    // Build the reqwest client
+    fn build_client(&self) -> Result<r#async::Client> {
+        let mut builder = r#async::ClientBuilder::new();
+        builder = builder.connect_timeout(self.connect_timeout);
+
+        if let Some(proxy) = self.proxy.reqwest_proxy(self.server.as_str())? {
+            builder = builder.proxy(proxy);
+        }
+
+        if self.accept_invalid_certs && self.certificate.is_empty() {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        if !self.certificate.is_empty() {
+            for cert in tls::certificates(self.certificate.as_str())? {
+                builder = builder.add_root_certificate(cert);
+            }
+            builder = builder.danger_accept_invalid_certs(false);
+        }
+
+        if let Some(ref identity) = self.identity {
+            builder = builder.identity(identity.reqwest_identity()?);
+        }
+
+        let client = builder.build()?;
+
+        Ok(client)
+    }
+
+    /// A convenience method to perform an API call.
+    ///
+    /// See `Client::call_and_check()`.
+    /// ```
+    /// client.call_and_check("show-host", json!({"name": "host1"})).await?;
+    /// ```
+    pub async fn call_and_check(
+        &mut self,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        let res = self.call(command, payload).await;
+        request::check(command, res)
+    }
+
+    /// Perform an API query.
+    ///
+    /// See `Client::query()`.
+    /// ```
+    /// let hosts = client.query("show-hosts", json!({"details-level": "full"})).await?;
+    /// for host in &hosts.objects {
+    ///     println!("{} - {}", host["name"], host["ipv4-address"]);
+    /// }
+    /// ```
+    pub async fn query(&mut self, command: &str, payload: serde_json::Value) -> Result<Response> {
+        let mut query = request::Query::new(payload)?;
+        let mut res = Response::new();
+
+        while query.has_next() {
+            res = self.call(command, query.payload()).await?;
+            query.add_page(&mut res)?;
+        }
+
+        Ok(query.finish(res))
+    }
+
+    /// A convenience method to perform an API query.
+    ///
+    /// See `Client::query_and_check()`.
+    /// ```
+    /// client.query_and_check("show-hosts", json!({"details-level": "full"})).await?;
+    /// ```
+    pub async fn query_and_check(
+        &mut self,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        let res = self.query(command, payload).await;
+        request::check(command, res)
+    }
+
+    /// Pick the highest API version supported by the server between min and max, inclusive,
+    /// and pin it with `AsyncClient::api_version()`.
+    ///
+    /// See `Client::negotiate_api_version()`.
+    /// ```
+    /// let version = client.negotiate_api_version(ApiVersion::new(1, 5), ApiVersion::new(1, 8))
+    ///     .await?;
+    /// ```
+    pub async fn negotiate_api_version(
+        &mut self,
+        min: ApiVersion,
+        max: ApiVersion
+        ) -> Result<ApiVersion>
+    {
+        let res = self.call("show-api-versions", json!({})).await?;
+        let (version, name) = request::negotiate_api_version(&res, min, max)?;
+        self.api_version = Some(name);
+
+        Ok(version)
+    }
+
+    // Run show-task and log it.
+    async fn show_task(&mut self, payload: serde_json::Value) -> Result<Response> {
+        let (url, headers, res) = self.send("show-task", &payload).await?;
+        self.log.record("show-task", url.as_str(), &headers, payload, &res)?;
+
+        Ok(res)
+    }
+
+    // Wait for a task to complete that returned a task-id.
+    async fn wait_for_task_id(&mut self, taskid: &str, command: &str) -> Result<Response> {
+        loop {
+            let res = self.show_task(request::show_task_payload(taskid)).await?;
+
+            if !request::task_in_progress(command, &res)? {
+                return Ok(res);
+            }
+
+            let delay = tokio_timer::Delay::new(Instant::now() + Duration::from_secs(5));
+            if let Err(e) = delay.compat().await {
+                let msg = format!("Failed to wait before checking the task again: {}", e);
+                return Err(Error::Custom(msg));
+            }
+        }
+    }
+
+    // Wait for multiple tasks to complete.
+    async fn wait_for_tasks(&mut self, ids: &[String], command: &str) -> Result<Response> {
+        for id in ids {
+            self.wait_for_task_id(id.as_str(), command).await?;
+        }
+
+        let mut res = self.show_task(request::show_task_payload(ids)).await?;
+        Response::check_tasks_status(&mut res);
+
+        Ok(res)
+    }
+
+    /// Set a DER or PEM encoded certificate, or a PEM bundle of certificates.
+    ///
+    /// See `Client::certificate()`.
+    pub fn certificate(&mut self, s: &str) {
+        self.certificate = s.to_string();
+        self.http_client = None;
+    }
+
+    /// Present a client certificate from a PKCS#12 file to the server.
+    ///
+    /// See `Client::identity_pkcs12()`.
+    pub fn identity_pkcs12(&mut self, file: &str, password: &str) {
+        self.identity = Some(ClientIdentity::Pkcs12 {
+            file: file.to_string(),
+            password: password.to_string(),
+        });
+        self.http_client = None;
+    }
+
+    /// Present a client certificate and key from PEM files to the server.
+    ///
+    /// See `Client::identity_pem()`.
+    pub fn identity_pem(&mut self, cert: &str, key: &str) {
+        self.identity = Some(ClientIdentity::Pem {
+            cert: cert.to_string(),
+            key: key.to_string(),
+        });
+        self.http_client = None;
+    }
+
+    /// Set the certificate validation. The default is false.
+    pub fn accept_invalid_certs(&mut self, b: bool) {
+        self.accept_invalid_certs = b;
+        self.http_client = None;
+    }
+
+    /// Set the proxy to use.
+    pub fn proxy(&mut self, s: &str) {
+        self.proxy.url = s.to_string();
+        self.http_client = None;
+    }
+
+    /// Set the user and password to authenticate with the proxy.
+    pub fn proxy_auth(&mut self, user: &str, password: &str) {
+        self.proxy.user = user.to_string();
+        self.proxy.password = password.to_string();
+        self.http_client = None;
+    }
+
+    /// Set the servers to connect to directly instead of through the proxy.
+    pub fn no_proxy(&mut self, list: &str) {
+        self.proxy.set_no_proxy(list);
+        self.http_client = None;
+    }
+
+    /// Use the HTTPS_PROXY and NO_PROXY environment variables. Default is false.
+    pub fn proxy_from_env(&mut self, b: bool) {
+        self.proxy.from_env = b;
+        self.http_client = None;
+    }
+
+    /// Set the path of the API on the server. Default is web_api.
+    pub fn api_path(&mut self, path: &str) {
+        self.api_path = request::api_path(path);
+    }
+
+    /// Pin the API version used in the URLs, such as /web_api/v1.5/show-hosts.
+    pub fn api_version(&mut self, version: ApiVersion) {
+        self.api_version = Some(version.to_string());
+    }
+
+    /// Set the timeout in seconds to connect to the server. The default is 30 seconds.
+    ///
+    /// This is also the timeout of the whole request,
+    /// unless `AsyncClient::request_timeout()` is set.
+    pub fn connect_timeout(&mut self, t: u64) {
+        self.connect_timeout = Duration::from_secs(t);
+        self.http_client = None;
+    }
+
+    /// Set the timeout in seconds of a whole request, from connecting to reading the response.
+    /// The default is the `AsyncClient::connect_timeout()`.
+    pub fn request_timeout(&mut self, t: u64) {
+        self.request_timeout = Some(Duration::from_secs(t));
+    }
+
+    /// Set the timeout in seconds of the requests of a command,
+    /// instead of the `AsyncClient::request_timeout()`.
+    pub fn command_timeout(&mut self, command: &str, t: u64) {
+        self.command_timeouts.insert(command.to_string(), Duration::from_secs(t));
+    }
+
+    /// Limit the rate of the requests sent to the server.
+    ///
+    /// See `Client::rate_limit()`.
+    pub fn rate_limit(&mut self, requests_per_second: f64, burst: u32) -> Result<()> {
+        self.rate_limiter = if requests_per_second == 0.0 {
+            None
+        }
+        else {
+            Some(Arc::new(RateLimiter::new(requests_per_second, burst)?))
+        };
+
+        Ok(())
+    }
+
+    /// Set the session-timeout in seconds. The default is 600 seconds.
+    pub fn session_timeout(&mut self, t: u64) {
+        self.session_timeout = t;
+    }
+
+    /// Set the Domain to login to.
+    pub fn domain(&mut self, s: &str) {
+        self.domain = s.to_string();
+    }
+
+    /// Login with a read only session. The default is false.
+    ///
+    /// Commands that change the configuration return `Error::ReadOnly`
+    /// without being sent, same as `Client::read_only()`.
+    pub fn read_only(&mut self, b: bool) {
+        self.read_only = b;
+    }
+
+    /// Set if a command changes the configuration.
+    ///
+    /// See `Client::mutating_command()`.
+    pub fn mutating_command(&mut self, command: &str, b: bool) {
+        self.mutating_commands.insert(command.to_string(), b);
+    }
+
+    // Check if a command changes the configuration, using the overrides first.
+    fn is_mutating(&self, command: &str) -> bool {
+        match self.mutating_commands.get(command) {
+            Some(t) => *t,
+            None => commands::is_mutating(command),
+        }
+    }
+
+    /// Continue the last session when logging in. The default is false.
+    pub fn continue_last_session(&mut self, b: bool) {
+        self.continue_last_session = b;
+    }
+
+    /// Get the base URL the commands are sent to.
+    pub fn base_url(&self) -> String {
+        request::base_url(self.scheme.as_str(), self.server.as_str(), self.port,
+                          self.api_path.as_str(), self.api_version.as_deref())
+    }
+
+    /// Get the sid after logging in.
+    pub fn sid(&self) -> &str {
+        self.sid.as_str()
+    }
+
+    /// Get the uid after logging in.
+    pub fn uid(&self) -> &str {
+        self.uid.as_str()
+    }
+
+    /// Get the api-server-version after logging in.
+    pub fn api_server_version(&self) -> &str {
+        self.api_server_version.as_str()
+    }
+
+    /// Get the LoginInfo after logging in.
+    pub fn login_info(&self) -> Option<&LoginInfo> {
+        self.login_info.as_ref()
+    }
+
+    /// Wait for an API call to complete. The default is true.
+    ///
+    /// See `Client::wait_for_task()`.
+    pub fn wait_for_task(&mut self, b: bool) {
+        self.wait_for_task = b;
+    }
+
+    /// Set the log file name that will contain the API calls.
+    ///
+    /// See `Client::log_file()`.
+    pub fn log_file(&mut self, s: &str) {
+        self.log.set_file(s);
+    }
+
+    /// Save the API calls to a file.
+    ///
+    /// See `Client::save_log()`.
+    pub fn save_log(&mut self) -> Result<()> {
+        self.log.save()
+    }
+
+    /// Show the login password or API key as clear text in the log file.
+    pub fn show_password(&mut self, b: bool) {
+        self.log.set_show_password(b);
+    }
+}
+
+impl std::fmt::Debug for AsyncClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AsyncClient")
+            .field("server", &self.server)
+            .field("port", &self.port)
+            .field("scheme", &self.scheme)
+            .field("api_path", &self.api_path)
+            .field("api_version", &self.api_version)
+            .field("certificate", &self.certificate)
+            .field("identity", &self.identity)
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("proxy", &self.proxy)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("command_timeouts", &self.command_timeouts)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("session_timeout", &self.session_timeout)
+            .field("domain", &self.domain)
+            .field("read_only", &self.read_only)
+            .field("mutating_commands", &self.mutating_commands)
+            .field("continue_last_session", &self.continue_last_session)
+            .field("sid", &self.sid)
+            .field("uid", &self.uid)
+            .field("api_server_version", &self.api_server_version)
+            .field("login_info", &self.login_info)
+            .field("wait_for_task", &self.wait_for_task)
+            .field("log_file", &self.log.file())
+            .field("show_password", &self.log.show_password())
+            .finish()
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::response::Response;
+use crate::error::{Error, Result};
+
+// The API calls recorded for the log file.
+//
+// Shared by the blocking and async Clients so both write the same log format.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct CallLog {
+    log_file: String,
+    all_calls: Vec<serde_json::Value>,
+    show_password: bool,
+}
+
+impl CallLog {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.log_file.is_empty()
+    }
+
+    pub(crate) fn file(&self) -> &str {
+        self.log_file.as_str()
+    }
+
+    pub(crate) fn set_file(&mut self, s: &str) {
+        self.log_file = s.to_string();
+    }
+
+    pub(crate) fn show_password(&self) -> bool {
+        self.show_password
+    }
+
+    pub(crate) fn set_show_password(&mut self, b: bool) {
+        self.show_password = b;
+    }
+
+    // Record an API call if the log file is set.
+    pub(crate) fn record(
+        &mut self,
+        command: &str,
+        url: &str,
+        headers: &HeaderMap,
+        payload: serde_json::Value,
+        res: &Response,
+        ) -> Result<()>
+    {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let j = json!({
+            "Request": self.request(command, url, headers, payload)?,
+            "Response": res
+        });
+
+        self.all_calls.push(j);
+
+        Ok(())
+    }
+
+    // Record an API call that failed without a Response, such as a retried network error.
+    pub(crate) fn record_error(
+        &mut self,
+        command: &str,
+        url: &str,
+        headers: &HeaderMap,
+        payload: serde_json::Value,
+        error: &Error,
+        ) -> Result<()>
+    {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let j = json!({
+            "Request": self.request(command, url, headers, payload)?,
+            "Error": error.to_string()
+        });
+
+        self.all_calls.push(j);
+
+        Ok(())
+    }
+
+    // The Request of a recorded call, with the password hidden on login.
+    fn request(
+        &self,
+        command: &str,
+        url: &str,
+        headers: &HeaderMap,
+        mut payload: serde_json::Value,
+        ) -> Result<serde_json::Value>
+    {
+        if command == "login" && !self.show_password {
+            for key in &["password", "api-key"] {
+                if let Some(obj) = payload.get_mut(*key) {
+                    *obj = json!("*****");
+                }
+            }
+        }
+
+        let mut map = HashMap::new();
+
+        for (k, v) in headers.iter() {
+            let k = k.as_str().to_string();
+            let v = v.to_str()?;
+            let v = v.to_string();
+
+            map.insert(k, v);
+        }
+
+        Ok(json!({
+            "headers": map,
+            "payload": payload,
+            "url": url
+        }))
+    }
+
+    // Save the recorded calls to the log file and clear them along with the log file.
+    pub(crate) fn save(&mut self) -> Result<()> {
+        if self.log_file.is_empty() {
+            let msg = String::from("log_file on the Client is not set");
+            return Err(Error::Custom(msg));
+        }
+
+        let mut f = File::create(self.log_file.as_str())?;
+
+        // Save all_calls with an indent of 4 spaces instead of 2 (the default)
+        let buf = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut ser = serde_json::Serializer::with_formatter(buf, formatter);
+
+        self.all_calls.serialize(&mut ser)?;
+        f.write_all(&ser.into_inner())?;
+
+        self.all_calls.clear();
+        self.log_file.clear();
+
+        Ok(())
+    }
+}
@@ -1,15 +1,33 @@
 use std::{fmt, time, thread};
-use std::fs::File;
-use std::io::{Read, Write};
+use std::cell::Cell;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::env;
+use std::process::Command;
 
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
+use reqwest::header::HeaderMap;
 
 use serde_json::json;
-use serde::Serialize;
-
+use serde::{Deserialize, Serialize};
+
+use crate::call_log::CallLog;
+use crate::commands;
+use crate::credentials::{CredentialProvider, Credentials};
+use crate::keepalive::{Keepalive, KeepaliveHandle};
+use crate::login_info::LoginInfo;
+use crate::pinned::{Connector, Pin, PinnedTransport};
+use crate::proxy::ProxySettings;
+use crate::rate_limit::RateLimiter;
+use crate::request;
 use crate::response::Response;
+use crate::retry::{self, RetryPolicy};
+use crate::session::Session;
+use crate::stream;
+use crate::tls::{self, ClientIdentity};
+use crate::transport::{ReqwestTransport, Transport};
+use crate::version::ApiVersion;
 use crate::error::{Error, Result};
 
 /// A Client to communicate with the API.
@@ -18,6 +36,8 @@ use crate::error::{Error, Result};
 /// are the most commonly used.
 ///
 /// The Client should be created and reused for multiple API calls.
+/// The connection to the server and the certificate are reused between calls,
+/// and only set up again after a certificate, proxy or timeout setting changes.
 ///
 /// ```
 /// use cp_api::Client;
@@ -34,55 +54,137 @@ use crate::error::{Error, Result};
 pub struct Client {
     server: String,
     port: u16,
+    scheme: String,
+    api_path: String,
+    // The pinned API version as written in the URLs.
+    api_version: Option<String>,
     certificate: String,
+    certificate_fingerprint: String,
+    known_servers: String,
+    trust_on_first_use: bool,
+    #[serde(skip)]
+    identity: Option<ClientIdentity>,
     accept_invalid_certs: bool,
-    proxy: String,
+    #[serde(skip)]
+    proxy: ProxySettings,
     connect_timeout: time::Duration,
+    request_timeout: Option<time::Duration>,
+    command_timeouts: HashMap<String, time::Duration>,
     session_timeout: u64,
     domain: String,
     read_only: bool,
+    mutating_commands: HashMap<String, bool>,
     continue_last_session: bool,
     sid: String,
     uid: String,
     api_server_version: String,
+    login_info: Option<LoginInfo>,
     wait_for_task: bool,
-    log_file: String,
-    all_calls: Vec<serde_json::Value>,
-    show_password: bool,
+    #[serde(flatten)]
+    log: CallLog,
+    auto_relogin: bool,
+    #[serde(skip)]
+    retry_policy: Option<RetryPolicy>,
+    #[serde(skip)]
+    rate_limiter: Option<Arc<RateLimiter>>,
+    relogin_count: u64,
+    #[serde(skip)]
+    credentials: Option<Credentials>,
+    #[serde(skip)]
+    credential_provider: Option<Box<dyn CredentialProvider>>,
+    #[serde(skip)]
+    keepalive: Option<Keepalive>,
+    #[serde(skip)]
+    http_client: Option<reqwest::Client>,
+    #[serde(skip)]
+    timeout_clients: HashMap<time::Duration, reqwest::Client>,
+    #[serde(skip)]
+    transport: Option<Arc<dyn Transport>>,
+    logout_on_drop: bool,
 }
 
 impl Client {
     /// Create a new Client to make API calls.
+    ///
+    /// The server is a host name or an IPv4 or IPv6 address, with or without brackets.
     /// ```
     /// let mut client = Client::new("192.168.1.10", 443);
+    /// let mut client = Client::new("fd00::10", 443);
     /// ```
     pub fn new(server: &str, port: u16) -> Self {
         Client {
-            server: String::from(server),
+            server: server.trim_start_matches('[').trim_end_matches(']').to_string(),
             port,
+            scheme: String::from("https"),
+            api_path: String::from("web_api"),
+            api_version: None,
             certificate: String::new(),
+            certificate_fingerprint: String::new(),
+            known_servers: String::new(),
+            trust_on_first_use: false,
+            identity: None,
             accept_invalid_certs: false,
-            proxy: String::new(),
+            proxy: ProxySettings::default(),
             connect_timeout: time::Duration::from_secs(30),
+            request_timeout: None,
+            command_timeouts: HashMap::new(),
             session_timeout: 600,
             domain: String::new(),
             read_only: false,
+            mutating_commands: HashMap::new(),
             continue_last_session: false,
             sid: String::with_capacity(50),
             uid: String::with_capacity(40),
             api_server_version: String::with_capacity(5),
+            login_info: None,
             wait_for_task: true,
-            log_file: String::new(),
-            all_calls: Vec::new(),
-            show_password: false,
+            log: CallLog::default(),
+            auto_relogin: false,
+            retry_policy: None,
+            rate_limiter: None,
+            relogin_count: 0,
+            credentials: None,
+            credential_provider: None,
+            keepalive: None,
+            http_client: None,
+            timeout_clients: HashMap::new(),
+            transport: None,
+            logout_on_drop: true,
         }
     }
 
+    /// Create a new Client from the base URL of the API.
+    ///
+    /// The commands are sent to the base URL followed by the command name.
+    /// This is used for the Gaia API, for a management server behind a reverse proxy
+    /// with a path prefix, or for a local stand-in server over plain HTTP.
+    /// The API path is web_api if the URL has no path.
+    ///
+    /// ```
+    /// let mut client = Client::from_url("https://[fd00::10]:443/web_api")?;
+    /// let mut gaia = Client::from_url("https://192.168.1.10/gaia_api")?;
+    /// let mut proxied = Client::from_url("https://mgmt.example.com/checkpoint/web_api")?;
+    /// let mut stand_in = Client::from_url("http://127.0.0.1:8080/web_api")?;
+    /// ```
+    pub fn from_url(base_url: &str) -> Result<Self> {
+        let parts = request::parse_base_url(base_url)?;
+
+        let mut client = Client::new(parts.server.as_str(), parts.port);
+        client.scheme = parts.scheme;
+        client.api_path = parts.api_path;
+
+        Ok(client)
+    }
+
     /// Login to the API.
     ///
     /// If the login is successful, the uid and api-server-version are stored in the Client.
     /// If the session is not read only, the sid will be stored as well.
     ///
+    /// The Management API only accepts a user and password or an API key to login.
+    /// Logging in with a certificate file, as SmartConsole allows, is not available
+    /// through the API. Use `Client::login_with_api_key()` for service accounts.
+    ///
     /// ```
     /// let mut client = Client::new("192.168.1.10", 443);
     /// client.certificate("/home/admin/cert.cer");
@@ -90,39 +192,269 @@ impl Client {
     /// assert!(login.is_success());
     /// ```
     pub fn login(&mut self, user: &str, pass: &str) -> Result<Response> {
-        let payload = json!({
-            "user": user,
-            "password": pass,
-            "domain": self.domain,
-            "session-timeout": self.session_timeout,
-            "read-only": self.read_only,
-            "continue-last-session": self.continue_last_session,
-        });
+        self.login_with(Credentials::password(user, pass))
+    }
+
+    /// Login to the API with an API key instead of a user and password.
+    ///
+    /// The session values are stored in the Client the same way as `Client::login()`.
+    /// The API key is obfuscated in the log file unless show_password is true.
+    ///
+    /// ```
+    /// let mut client = Client::new("192.168.1.10", 443);
+    /// client.certificate("/home/admin/cert.cer");
+    /// let login = client.login_with_api_key("eXaMpLeKeY123==")?;
+    /// assert!(login.is_success());
+    /// ```
+    pub fn login_with_api_key(&mut self, api_key: &str) -> Result<Response> {
+        self.login_with(Credentials::api_key(api_key))
+    }
+
+    /// Login to the API with the Credentials from the credential_provider.
+    ///
+    /// The same provider is used to login again if auto_relogin is set.
+    ///
+    /// ```
+    /// let mut client = Client::new("192.168.1.10", 443);
+    /// client.credential_provider(EnvCredentials::new());
+    /// let login = client.login_with_provider()?;
+    /// assert!(login.is_success());
+    /// ```
+    pub fn login_with_provider(&mut self) -> Result<Response> {
+        let credentials = self.provided_credentials()?;
+        self.login_with(credentials)
+    }
 
-        let login = self.call("login", payload)?;
+    // Get the Credentials from the credential_provider.
+    fn provided_credentials(&self) -> Result<Credentials> {
+        match self.credential_provider {
+            Some(ref provider) => provider.credentials(self.server.as_str(), self.domain.as_str()),
+            None => {
+                let msg = String::from("credential_provider on the Client is not set");
+                Err(Error::Custom(msg))
+            }
+        }
+    }
+
+    /// Login as root on the management server without credentials.
+    ///
+    /// This runs `mgmt_cli login -r true`, so it only works on the management server itself.
+    /// `$CPDIR/bin/mgmt_cli` is used if CPDIR is set, otherwise mgmt_cli is found in the PATH.
+    /// The Client should be created for 127.0.0.1 and the port of the API server.
+    ///
+    /// The session values are stored in the Client the same way as `Client::login()`.
+    /// If the Domain is set, the login is to that Domain on a Multi-Domain Server.
+    ///
+    /// ```
+    /// let mut client = Client::new("127.0.0.1", 443);
+    /// client.domain("Domain A");
+    /// let login = client.login_as_root()?;
+    /// assert!(login.is_success());
+    /// ```
+    pub fn login_as_root(&mut self) -> Result<Response> {
+        self.login_with(Credentials::Root)
+    }
+
+    // Run mgmt_cli to login as root and return the login as a Response.
+    fn run_login_as_root(&mut self) -> Result<Response> {
+        let mgmt_cli = match env::var("CPDIR") {
+            Ok(t) => format!("{}/bin/mgmt_cli", t),
+            Err(_) => String::from("mgmt_cli"),
+        };
+
+        let mut args = vec![
+            String::from("login"),
+            String::from("-r"), String::from("true"),
+            String::from("-f"), String::from("json"),
+            String::from("--port"), self.port.to_string(),
+        ];
+
+        if !self.domain.is_empty() {
+            args.push(String::from("-d"));
+            args.push(self.domain.clone());
+        }
+
+        args.push(String::from("session-timeout"));
+        args.push(self.session_timeout.to_string());
+        args.push(String::from("read-only"));
+        args.push(self.read_only.to_string());
+        args.push(String::from("continue-last-session"));
+        args.push(self.continue_last_session.to_string());
+
+        let output = Command::new(mgmt_cli.as_str()).args(&args).output()?;
+
+        let data: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+            Ok(t) => t,
+            Err(e) => {
+                let msg = format!("Output from '{} login -r true' is not valid JSON: {}. \
+                                   stderr: {}", mgmt_cli, e,
+                                   String::from_utf8_lossy(&output.stderr).trim());
+                return Err(Error::Custom(msg));
+            }
+        };
+
+        // mgmt_cli does not return the HTTP status, so a failed login is a generic client error
+        let status = if output.status.success() { 200 } else { 400 };
+        let url = format!("{} {}", mgmt_cli, args.join(" "));
+        let login = Response::from_data(status, url.as_str(), data);
+
+        let payload = json!({"args": args});
+        self.log.record("login-as-root", url.as_str(), &HeaderMap::new(), payload, &login)?;
+
+        Ok(login)
+    }
+
+    // Login with the Credentials and store the session values.
+    fn login_with(&mut self, credentials: Credentials) -> Result<Response> {
+        let login = match credentials {
+            Credentials::Root => self.run_login_as_root()?,
+            _ => {
+                let payload = request::login_payload(&credentials,
+                                                     self.domain.as_str(),
+                                                     self.session_timeout,
+                                                     self.read_only,
+                                                     self.continue_last_session);
+
+                self.call("login", payload)?
+            }
+        };
 
         if login.is_success() {
-            self.sid = match login.data["sid"].as_str() {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("sid", json!(login)))
-            }.to_string();
+            self.store_session(&login)?;
+            self.credentials = Some(credentials);
+        }
+
+        Ok(login)
+    }
+
+    // Store the session values from a successful login.
+    fn store_session(&mut self, login: &Response) -> Result<()> {
+        let session = request::login_session(login, self.read_only)?;
+
+        self.sid = session.sid;
+        self.api_server_version = session.api_server_version;
+
+        if !self.read_only {
+            self.uid = session.uid;
+        }
+
+        self.login_info = Some(LoginInfo::from_response(login)?);
+
+        if let Some(ref keepalive) = self.keepalive {
+            keepalive.set_sid(self.sid.as_str());
+        }
+
+        Ok(())
+    }
 
-            self.api_server_version = match login.data["api-server-version"].as_str() {
+    /// Login to a Domain from a session on the Multi-Domain Server.
+    ///
+    /// A new Client is returned with the Domain session.
+    /// It uses the same settings as this Client, except for the log_file which is not set.
+    ///
+    /// ```
+    /// let mut mds = Client::new("192.168.1.10", 443);
+    /// mds.login("user", "pass")?;
+    ///
+    /// let mut domain = mds.login_to_domain("Domain A")?;
+    /// domain.call("show-host", json!({"name": "host1"}))?;
+    /// ```
+    pub fn login_to_domain(&mut self, domain: &str) -> Result<Client> {
+        let login = self.call("login-to-domain", json!({"domain": domain}))?;
+
+        if login.is_not_success() {
+            let msg = format!("Failed to login to domain '{}'. status: {}, code: {}, message: {}",
+                              domain, login.status(), login.data["code"], login.data["message"]);
+            return Err(Error::Custom(msg));
+        }
+
+        let mut client = Client::new(self.server.as_str(), self.port);
+        client.scheme = self.scheme.clone();
+        client.api_path = self.api_path.clone();
+        client.api_version = self.api_version.clone();
+        client.http_client = self.http_client.clone();
+        client.timeout_clients = self.timeout_clients.clone();
+        client.transport = self.transport.clone();
+        client.certificate = self.certificate.clone();
+        client.certificate_fingerprint = self.certificate_fingerprint.clone();
+        client.known_servers = self.known_servers.clone();
+        client.trust_on_first_use = self.trust_on_first_use;
+        client.identity = self.identity.clone();
+        client.accept_invalid_certs = self.accept_invalid_certs;
+        client.proxy = self.proxy.clone();
+        client.connect_timeout = self.connect_timeout;
+        client.request_timeout = self.request_timeout;
+        client.command_timeouts = self.command_timeouts.clone();
+        client.session_timeout = self.session_timeout;
+        client.domain = domain.to_string();
+        client.read_only = self.read_only;
+        client.mutating_commands = self.mutating_commands.clone();
+        client.wait_for_task = self.wait_for_task;
+        client.log.set_show_password(self.log.show_password());
+        client.auto_relogin = self.auto_relogin;
+        client.retry_policy = self.retry_policy.clone();
+        client.rate_limiter = self.rate_limiter.clone();
+        client.credentials = self.credentials.clone();
+
+        client.store_session(&login)?;
+
+        Ok(client)
+    }
+
+    // Login again after the session expired.
+    //
+    // The credential_provider is used if set, otherwise the Credentials from the last login.
+    fn relogin(&mut self) -> Result<Response> {
+        let credentials = match self.credential_provider {
+            Some(_) => self.provided_credentials()?,
+            None => match self.credentials.clone() {
                 Some(t) => t,
-                None => return Err(Error::InvalidResponse("api-server-version", json!(login)))
-            }.to_string();
-
-            if !self.read_only {
-                self.uid = match login.data["uid"].as_str() {
-                    Some(t) => t,
-                    None => return Err(Error::InvalidResponse("uid", json!(login)))
-                }.to_string();
+                None => {
+                    let msg = String::from("The session expired and there are no \
+                                            credentials to login again");
+                    return Err(Error::Custom(msg));
+                }
             }
+        };
+
+        self.sid.clear();
+        let login = self.login_with(credentials)?;
+
+        if login.is_not_success() {
+            let msg = format!("Failed to login again after the session expired. \
+                               Error code: {}, message: {}",
+                               login.data["code"], login.data["message"]);
+            return Err(Error::Custom(msg));
         }
 
+        self.relogin_count += 1;
+
         Ok(login)
     }
 
+    // Login again after the session expired, to send the command again in the new session.
+    //
+    // The unpublished changes of the expired session are lost, so a command
+    // that changes the configuration is not sent again, it would publish or
+    // change an empty session.
+    fn relogin_to_retry(&mut self, command: &str) -> Result<()> {
+        self.relogin()?;
+
+        if self.is_mutating(command) {
+            let msg = format!("The session expired and its unpublished changes were lost, \
+                               '{}' was not sent again", command);
+            return Err(Error::Custom(msg));
+        }
+
+        Ok(())
+    }
+
+    // Check if a Response failed because the session expired or the sid is no longer valid.
+    fn is_session_expired(command: &str, res: &Response) -> bool {
+        command != "login" && command != "logout" &&
+            res.is_client_error() && res.data["code"] == "generic_err_wrong_session_id"
+    }
+
     /// Logout of the API.
     ///
     /// If the logout was successful, the sid, uid, and api-server-version are cleared
@@ -133,12 +465,16 @@ impl Client {
     /// assert!(logout.is_success());
     /// ```
     pub fn logout(&mut self) -> Result<Response> {
+        self.stop_keepalive();
+
         let logout = self.call("logout", json!({}))?;
 
         if logout.is_success() {
             self.sid.clear();
             self.uid.clear();
             self.api_server_version.clear();
+            self.login_info = None;
+            self.credentials = None;
         }
 
         Ok(logout)
@@ -170,74 +506,249 @@ impl Client {
     /// assert!(publish.is_success());
     /// ```
     pub fn call(&mut self, command: &str, payload: serde_json::Value) -> Result<Response> {
-        let url = format!("https://{}:{}/web_api/{}", self.server, self.port, command);
-        let headers = self.headers()?;
-        let headers2 = headers.clone();
+        if self.is_refused(command) {
+            return Err(Error::ReadOnly(command.to_string()));
+        }
 
-        let reqwest_client = self.build_client(headers)?;
+        let (mut url, mut headers, mut res) =
+            self.send_with_retry(command, &payload, |client| client.send(command, &payload))?;
 
-        let mut reqwest_response = reqwest_client.post(url.as_str())
-            .json(&payload)
-            .send()?;
+        if self.auto_relogin && Client::is_session_expired(command, &res) {
+            self.log.record(command, url.as_str(), &headers, payload.clone(), &res)?;
 
-        let mut res = Response::set(&mut reqwest_response)?;
+            self.relogin_to_retry(command)?;
 
-        if self.wait_for_task == true && res.is_success() && command != "show-task" {
-            if res.data.get("task-id").is_some() {
-                res = self._wait_for_task(res.data["task-id"].as_str().unwrap(), command)?;
+            let (u, h, r) =
+                self.send_with_retry(command, &payload, |client| client.send(command, &payload))?;
+            url = u;
+            headers = h;
+            res = r;
+        }
+
+        if self.wait_for_task && res.is_success() && command != "show-task" {
+            match request::task_ids(&res) {
+                Some((true, ids)) => res = self._wait_for_task(ids[0].as_str(), command)?,
+                Some((false, ids)) => res = self._wait_for_tasks(&ids, command)?,
+                None => (),
             }
-            else if res.data.get("tasks").is_some() {
-                res = self._wait_for_tasks(res.data["tasks"].as_array().unwrap(), command)?;
+        }
+
+        self.log.record(command, url.as_str(), &headers, payload, &res)?;
+
+        Ok(res)
+    }
+
+    // Send a Request with send, retrying the transient failures with the RetryPolicy.
+    //
+    // The failed attempts are recorded in the log, the last attempt is returned.
+    fn send_with_retry<S>(
+        &mut self,
+        command: &str,
+        payload: &serde_json::Value,
+        mut send: S
+        ) -> Result<(String, HeaderMap, Response)>
+        where S: FnMut(&mut Client) -> Result<(String, HeaderMap, Response)>
+    {
+        let policy = match self.retry_policy {
+            Some(ref policy) if policy.retries(command) => policy.clone(),
+            _ => return send(self),
+        };
+
+        let mut attempt = 1;
+        loop {
+            let sent = send(self);
+            if attempt >= policy.max_attempts {
+                return sent;
+            }
+
+            match sent {
+                Ok((url, headers, res)) => {
+                    if !retry::is_retryable_response(&res) {
+                        return Ok((url, headers, res));
+                    }
+                    self.log.record(command, url.as_str(), &headers, payload.clone(), &res)?;
+                },
+                Err(e) => {
+                    if !retry::is_retryable_error(&e) {
+                        return Err(e);
+                    }
+                    let url = request::url(self.base_url().as_str(), command);
+                    let headers = request::headers(self.sid.as_str())?;
+                    self.log.record_error(command, url.as_str(), &headers, payload.clone(), &e)?;
+                },
             }
+
+            thread::sleep(policy.delay(attempt));
+            attempt += 1;
+        }
+    }
+
+    // Send a Request and return the URL and headers used along with the Response.
+    fn send(
+        &mut self,
+        command: &str,
+        payload: &serde_json::Value
+        ) -> Result<(String, HeaderMap, Response)>
+    {
+        let url = request::url(self.base_url().as_str(), command);
+        let headers = request::headers(self.sid.as_str())?;
+
+        if let Some(ref limiter) = self.rate_limiter {
+            thread::sleep(limiter.reserve());
         }
 
-        if !self.log_file.is_empty() {
-            self.update_calls(command, url.as_str(), headers2, payload, &res)?;
+        let sent = self.current_transport(command)?.send(command, &headers, payload)?;
+        let res = Response::from_parts(sent.status, url.as_str(), &sent.headers, &sent.body)?;
+
+        if let Some(ref keepalive) = self.keepalive {
+            keepalive.touch();
         }
 
-        Ok(res)
+        Ok((url, headers, res))
     }
 
-    // Generate the headers for a Request
-    fn headers(&self) -> Result<HeaderMap> {
-        let mut headers = HeaderMap::new();
+    // Send a query page, passing each object to on_object while the body is parsed.
+    //
+    // The Response has the rest of the page, without the objects.
+    // An error from on_object is put in callback_error, and a placeholder error
+    // that is not retried is returned.
+    fn send_page<F>(
+        &mut self,
+        command: &str,
+        payload: &serde_json::Value,
+        on_object: &mut F,
+        callback_error: &Cell<Option<Error>>
+        ) -> Result<(String, HeaderMap, Response)>
+        where F: FnMut(serde_json::Value) -> Result<()>
+    {
+        let url = request::url(self.base_url().as_str(), command);
+        let headers = request::headers(self.sid.as_str())?;
+
+        if let Some(ref limiter) = self.rate_limiter {
+            thread::sleep(limiter.reserve());
+        }
+
+        let sent = self.current_transport(command)?.send_streaming(command, &headers, payload)?;
 
-        headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert(USER_AGENT, HeaderValue::from_static("cp_api"));
+        let mut count = 0;
+        let mut counted = |object| {
+            count += 1;
+            on_object(object)
+        };
+        let data = match stream::parse_page(sent.status, &sent.headers, sent.body, &mut counted) {
+            Ok(t) => t,
+            Err(stream::PageError::Callback(e)) => {
+                callback_error.set(Some(e));
+                let msg = format!("The objects of '{}' were stopped by the callback", command);
+                return Err(Error::Custom(msg));
+            },
+            // The objects already handled would be handled again if the page was retried.
+            Err(stream::PageError::Read(e)) if count > 0 => {
+                let msg = format!("Failed to read the page of '{}' after {} objects: {}",
+                                  command, count, e);
+                return Err(Error::Custom(msg));
+            },
+            Err(stream::PageError::Read(e)) => return Err(e),
+        };
 
-        if !self.sid.is_empty() {
-            let n = HeaderName::from_static("x-chkp-sid");
-            let v = HeaderValue::from_str(self.sid.as_str())?;
+        let res = Response::from_value(sent.status, url.as_str(), &sent.headers, data)?;
 
-            headers.insert(n, v);
+        if let Some(ref keepalive) = self.keepalive {
+            keepalive.touch();
         }
 
-        Ok(headers)
+        Ok((url, headers, res))
+    }
+
+    // Get the Transport set with Client::transport(), the PinnedTransport when the
+    // certificate is pinned, or the default ReqwestTransport with the read timeout of the command.
+    fn current_transport(&mut self, command: &str) -> Result<Arc<dyn Transport>> {
+        if let Some(ref transport) = self.transport {
+            return Ok(Arc::clone(transport));
+        }
+
+        if let Some(pin) = self.pin()? {
+            if self.scheme != "https" {
+                let msg = format!("The server certificate can't be checked over {}, use https",
+                                  self.scheme);
+                return Err(Error::Custom(msg));
+            }
+
+            let timeout = match self.command_timeouts.get(command) {
+                Some(t) => *t,
+                None => self.default_timeout(),
+            };
+
+            let transport = PinnedTransport::new(self.base_url().as_str(),
+                                                 self.connector(timeout)?, pin);
+
+            return Ok(Arc::new(transport));
+        }
+
+        let reqwest_client = self.http_client(command)?;
+        let transport = ReqwestTransport::with_base_url(self.base_url().as_str(), reqwest_client);
+
+        Ok(Arc::new(transport))
+    }
+
+    // Get the reqwest client for a command, building it if a setting changed since the last call.
+    //
+    // The reqwest client is reused so the connections and certificate are reused as well.
+    // reqwest only sets the timeout on the client, so a command with its own timeout
+    // has its own reqwest client, which is rebuilt along with the default one.
+    fn http_client(&mut self, command: &str) -> Result<reqwest::Client> {
+        let client = match self.http_client {
+            Some(ref client) => client.clone(),
+            None => {
+                let client = self.build_client(self.default_timeout())?;
+                self.http_client = Some(client.clone());
+                self.timeout_clients.clear();
+                client
+            }
+        };
+
+        let timeout = match self.command_timeouts.get(command) {
+            Some(t) if *t != self.default_timeout() => *t,
+            _ => return Ok(client),
+        };
+
+        if let Some(client) = self.timeout_clients.get(&timeout) {
+            return Ok(client.clone());
+        }
+
+        let client = self.build_client(timeout)?;
+        self.timeout_clients.insert(timeout, client.clone());
+
+        Ok(client)
+    }
+
+    // The timeout of the whole request, the connection timeout unless it was set.
+    fn default_timeout(&self) -> time::Duration {
+        self.request_timeout.unwrap_or(self.connect_timeout)
     }
 
     // Build the reqwest client
-    fn build_client(&self, headers: HeaderMap) -> Result<reqwest::Client> {
+    fn build_client(&self, timeout: time::Duration) -> Result<reqwest::Client> {
         let mut builder = reqwest::ClientBuilder::new();
-        builder = builder.default_headers(headers);
-        builder = builder.timeout(self.connect_timeout);
+        builder = builder.connect_timeout(self.connect_timeout);
+        builder = builder.timeout(timeout);
 
-        if !self.proxy.is_empty() {
-            builder = builder.proxy(reqwest::Proxy::https(self.proxy.as_str())?);
+        if let Some(proxy) = self.proxy.reqwest_proxy(self.server.as_str())? {
+            builder = builder.proxy(proxy);
         }
 
-        if self.accept_invalid_certs == true && self.certificate.is_empty() {
-            builder = builder.danger_accept_invalid_certs(true);
+        if !self.certificate.is_empty() {
+            for cert in tls::certificates(self.certificate.as_str())? {
+                builder = builder.add_root_certificate(cert);
+            }
         }
 
-        if !self.certificate.is_empty() {
-            let mut buf: Vec<u8> = Vec::new();
-            File::open(self.certificate.as_str())?
-                .read_to_end(&mut buf)?;
+        if let Some(ref identity) = self.identity {
+            builder = builder.identity(identity.reqwest_identity()?);
+        }
 
-            let cert = reqwest::Certificate::from_der(&buf)?;
-            builder = builder.add_root_certificate(cert);
-            builder = builder.danger_accept_invalid_certs(false);
+        if self.accept_invalid_certs && self.certificate.is_empty() {
+            builder = builder.danger_accept_invalid_certs(true);
         }
 
         let client = builder.build()?;
@@ -245,6 +756,26 @@ impl Client {
         Ok(client)
     }
 
+    // Get how the server certificate is pinned, if it is.
+    fn pin(&self) -> Result<Option<Pin>> {
+        if !self.certificate_fingerprint.is_empty() {
+            Ok(Some(Pin::fingerprint(self.certificate_fingerprint.as_str())?))
+        }
+        else if !self.known_servers.is_empty() {
+            Ok(Some(Pin::known_servers(self.known_servers.as_str(), self.server.as_str(),
+                                       self.port, self.trust_on_first_use)))
+        }
+        else {
+            Ok(None)
+        }
+    }
+
+    // Build the Connector that sets up the TLS connections of the PinnedTransport.
+    fn connector(&self, timeout: time::Duration) -> Result<Connector> {
+        Connector::new(self.server.as_str(), self.port, &self.proxy, self.identity.as_ref(),
+                       self.connect_timeout, timeout)
+    }
+
     /// A convenience method to perform an API call.
     ///
     /// This will check that the call to the server and
@@ -268,21 +799,22 @@ impl Client {
         payload: serde_json::Value
         ) -> Result<Response>
     {
-        let res = match self.call(command, payload) {
-            Ok(t) => t,
-            Err(e) => {
-                let msg = format!("Failed to run command, '{}': {}", command, e);
-                return Err(Error::Custom(msg));
-            }
-        };
-
-        if res.is_not_success() {
-            let msg = format!("'{}' was not successful. status: {}, code: {}, message: {}",
-                                command, res.status(), res.data["code"], res.data["message"]);
-            return Err(Error::Custom(msg));
-        }
+        let res = self.call(command, payload);
+        request::check(command, res)
+    }
 
-        Ok(res)
+    /// Start a scoped Session with a description.
+    ///
+    /// The changes made with the Session are published with `Session::commit()`
+    /// and discarded if the Session is dropped without being committed.
+    ///
+    /// ```
+    /// let mut session = client.session("Add web servers")?;
+    /// session.call_and_check("add-host", json!({"name": "web1", "ip-address": "10.1.1.21"}))?;
+    /// session.commit()?;
+    /// ```
+    pub fn session(&mut self, description: &str) -> Result<Session<'_>> {
+        Session::new(self, description)
     }
 
     /// Perform an API query.
@@ -306,95 +838,88 @@ impl Client {
     /// }
     /// ```
     pub fn query(&mut self, command: &str, payload: serde_json::Value) -> Result<Response> {
+        let mut query = request::Query::new(payload)?;
         let mut res = Response::new();
-        let mut vec: Vec<serde_json::Value> = Vec::new();
-
-        let limit = match payload.get("limit") {
-            Some(t) => match t.as_u64() {
-                Some(v) => v,
-                None => 50
-            },
-            None => 50
-        };
-
-        let mut offset = match payload.get("offset") {
-            Some(t) => match t.as_u64() {
-                Some(v) => v,
-                None => 0
-            },
-            None => 0
-        };
-
-        let mut payload2 = self.build_query_payload(payload, offset)?;
 
-        let mut to = 0;
-        let mut total = 1;
+        while query.has_next() {
+            res = self.call(command, query.payload())?;
+            query.add_page(&mut res)?;
+        }
 
-        while to != total {
-            res = self.call(command, payload2.clone())?;
+        Ok(query.finish(res))
+    }
 
-            if res.is_not_success() {
-                let msg = format!("Received an unsuccessful Response from the API \
-                                   while running a query. Error code: {}, message: {}",
-                                   res.data["code"], res.data["message"]);
-                return Err(Error::Custom(msg));
-            }
+    /// Perform an API query and handle each object while the Response is received.
+    ///
+    /// This is for queries with too many objects to keep in memory.
+    /// The objects of each page are parsed one at a time and passed to on_object,
+    /// instead of being stored in the Response objects field.
+    /// Returning an error from on_object stops the query and returns that error.
+    ///
+    /// The Response of the last page is returned without its objects.
+    ///
+    /// ```
+    /// let mut count = 0;
+    /// let res = client.query_each("show-objects", json!({"limit": 500}), |object| {
+    ///     count += 1;
+    ///     println!("{} - {}", object["name"], object["type"]);
+    ///     Ok(())
+    /// })?;
+    /// assert!(res.is_success());
+    /// assert_eq!(res.data["total"], count);
+    /// ```
+    pub fn query_each<F>(
+        &mut self,
+        command: &str,
+        payload: serde_json::Value,
+        mut on_object: F
+        ) -> Result<Response>
+        where F: FnMut(serde_json::Value) -> Result<()>
+    {
+        if self.is_refused(command) {
+            return Err(Error::ReadOnly(command.to_string()));
+        }
 
-            to = match res.data["to"].as_u64() {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("to", json!(res)))
-            };
+        let mut query = request::Query::new(payload)?;
+        let mut res = Response::new();
 
-            total = match res.data["total"].as_u64() {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("total", json!(res)))
-            };
+        // The error of on_object is returned as it is, instead of the error of the page.
+        let callback_error = Cell::new(None);
 
-            let mut objects = match res.data["objects"].as_array_mut() {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("objects", json!(res)))
+        while query.has_next() {
+            let payload = query.payload();
+            let mut send = |client: &mut Client| {
+                client.send_page(command, &payload, &mut on_object, &callback_error)
             };
 
-            vec.append(&mut objects);
-
-            offset += limit;
-
-            if let Some(obj) = payload2.get_mut("offset") {
-                *obj = json!(offset);
+            let sent = self.send_with_retry(command, &payload, &mut send);
+            if let Some(e) = callback_error.take() {
+                return Err(e);
             }
-            else {
-                let msg = String::from("Failed to get the offset to update from payload");
-                return Err(Error::Custom(msg));
-            }
-        }
+            let (mut url, mut headers, mut page) = sent?;
 
-        res.objects = vec;
-        res.data = json!({});
+            if self.auto_relogin && Client::is_session_expired(command, &page) {
+                self.log.record(command, url.as_str(), &headers, payload.clone(), &page)?;
 
-        Ok(res)
-    }
+                self.relogin_to_retry(command)?;
 
-    // Build the query payload
-    fn build_query_payload(&self, mut payload: serde_json::Value, offset: u64) -> Result<serde_json::Value> {
-        let payload_map = match payload.as_object_mut() {
-            Some(t) => t,
-            None => {
-                let msg = String::from("Failed to parse payload for query");
-                return Err(Error::Custom(msg));
+                let sent = self.send_with_retry(command, &payload, &mut send);
+                if let Some(e) = callback_error.take() {
+                    return Err(e);
+                }
+                let (u, h, r) = sent?;
+                url = u;
+                headers = h;
+                page = r;
             }
-        };
 
-        let offset_json = match serde_json::to_value(offset) {
-            Ok(t) => t,
-            Err(e) => {
-                let msg = format!("Failed to convert offset for query: {}", e);
-                return Err(Error::Custom(msg));
-            }
-        };
+            self.log.record(command, url.as_str(), &headers, payload, &page)?;
 
-        payload_map.insert("offset".to_string(), offset_json);
+            query.next_page(&page)?;
+            res = page;
+        }
 
-        Ok(json!(payload_map))
+        Ok(res)
     }
 
     /// A convenience method to perform an API query.
@@ -420,32 +945,129 @@ impl Client {
         payload: serde_json::Value
         ) -> Result<Response>
     {
-        let res = match self.query(command, payload) {
-            Ok(t) => t,
-            Err(e) => {
-                let msg = format!("Failed to run command, '{}': {}", command, e);
-                return Err(Error::Custom(msg));
-            }
-        };
-
-        if res.is_not_success() {
-            let msg = format!("'{}' was not successful. status: {}, code: {}, message: {}",
-                                command, res.status(), res.data["code"], res.data["message"]);
-            return Err(Error::Custom(msg));
-        }
-
-        Ok(res)
+        let res = self.query(command, payload);
+        request::check(command, res)
     }
 
-    /// Set a binary DER encoded certificate.
+    /// Set a certificate to trust.
+    ///
+    /// The file can be a binary DER encoded certificate, a PEM encoded certificate
+    /// or a PEM bundle with multiple certificates, such as the ICA and its root.
     ///
     /// If a certificate is set, accept_invalid_certs will be ignored.
     ///
     /// ```
     /// client.certificate("/home/admin/mycert.cer");
+    /// client.certificate("/home/admin/ica_bundle.pem");
     /// ```
     pub fn certificate(&mut self, s: &str) {
         self.certificate = s.to_string();
+        self.http_client = None;
+    }
+
+    /// Pin the SHA-256 fingerprint of the server certificate.
+    ///
+    /// The fingerprint is checked on every connection to the server, including through
+    /// the proxy, before the command is sent on it. Each command is sent over a new connection.
+    /// When it matches, the server certificate is trusted even if it is self-signed
+    /// or signed by the ICA, and the certificate and accept_invalid_certs are ignored.
+    /// When it does not match, the call returns `Error::FingerprintMismatch`
+    /// with the fingerprint that was observed.
+    ///
+    /// The fingerprint is hex, with or without colons, as shown by
+    /// `openssl x509 -fingerprint -sha256` or `Client::server_fingerprint()`.
+    ///
+    /// ```
+    /// client.certificate_fingerprint("82:00:F1:5B:2A:75:CF:D1:07:87:DE:39:BE:A9:36:09:\
+    ///                                 27:31:D0:12:97:2C:D6:D0:F6:B4:D2:B6:4E:33:3E:01");
+    /// ```
+    pub fn certificate_fingerprint(&mut self, s: &str) {
+        self.certificate_fingerprint = s.to_string();
+        self.http_client = None;
+    }
+
+    /// Check the server certificate against a known servers file, the same as mgmt_cli.
+    ///
+    /// The SHA-256 fingerprint of the certificate is checked on every connection to the server,
+    /// the same as `Client::certificate_fingerprint()`. When it does not match the fingerprint
+    /// in the file, the call returns `Error::KnownServerChanged` with the known and new
+    /// fingerprints. If the change is expected, remove the line of the server from the file.
+    ///
+    /// A server that is not in the file returns `Error::UnknownServer` with the fingerprint
+    /// of its certificate, unless `Client::trust_on_first_use()` is set.
+    ///
+    /// Each line of the file has the server and port and the fingerprint:
+    ///
+    /// ```text
+    /// 192.168.1.10:443 82:00:F1:5B:2A:75:CF:D1:07:87:DE:39:BE:A9:36:09:27:31:D0:12:...
+    /// ```
+    ///
+    /// This is ignored if certificate_fingerprint is set,
+    /// and the certificate and accept_invalid_certs are ignored when this is set.
+    /// ```
+    /// client.known_servers("/home/admin/.cp_api_known_servers");
+    /// ```
+    pub fn known_servers(&mut self, file: &str) {
+        self.known_servers = file.to_string();
+        self.http_client = None;
+    }
+
+    /// Add a server that is not in the known servers file to it on the first connection.
+    ///
+    /// The default is false, since the certificate is trusted without being checked.
+    /// Only set this when the network path to the server is trusted, such as on the first run
+    /// of a script right after checking the fingerprint shown by `cpconfig` on the server.
+    /// ```
+    /// client.known_servers("/home/admin/.cp_api_known_servers");
+    /// client.trust_on_first_use(true);
+    /// ```
+    pub fn trust_on_first_use(&mut self, b: bool) {
+        self.trust_on_first_use = b;
+    }
+
+    /// Present a client certificate from a PKCS#12 file to the server.
+    ///
+    /// This is needed when the management server is behind a reverse proxy
+    /// that requires client certificates. The certificate is presented on every connection,
+    /// including login, task polling and the keepalive.
+    /// ```
+    /// client.identity_pkcs12("/home/admin/client.p12", "p12_password");
+    /// ```
+    pub fn identity_pkcs12(&mut self, file: &str, password: &str) {
+        self.identity = Some(ClientIdentity::Pkcs12 {
+            file: file.to_string(),
+            password: password.to_string(),
+        });
+        self.http_client = None;
+    }
+
+    /// Present a client certificate and key from PEM files to the server.
+    ///
+    /// The same as `Client::identity_pkcs12()`. The key must not be encrypted,
+    /// and any certificates after the first one in the certificate file are sent as its chain.
+    ///
+    /// This is only supported where the TLS library is OpenSSL, such as Linux.
+    /// On Windows and macOS, convert the files with `openssl pkcs12 -export`.
+    /// ```
+    /// client.identity_pem("/home/admin/client.pem", "/home/admin/client.key");
+    /// ```
+    pub fn identity_pem(&mut self, cert: &str, key: &str) {
+        self.identity = Some(ClientIdentity::Pem {
+            cert: cert.to_string(),
+            key: key.to_string(),
+        });
+        self.http_client = None;
+    }
+
+    /// Connect to the server and get the SHA-256 fingerprint of its certificate.
+    ///
+    /// The certificate is not validated, so only use the fingerprint to pin it
+    /// after checking it is the expected one, such as with `cpconfig` on the server.
+    /// ```
+    /// println!("{}", client.server_fingerprint()?);
+    /// ```
+    pub fn server_fingerprint(&self) -> Result<String> {
+        self.connector(self.connect_timeout)?.server_fingerprint()
     }
 
     /// Set the certificate validation.
@@ -456,24 +1078,145 @@ impl Client {
     /// ```
     pub fn accept_invalid_certs(&mut self, b: bool) {
         self.accept_invalid_certs = b;
+        self.http_client = None;
     }
 
     /// Set the proxy to use.
     ///
-    /// This will proxy all HTTPS traffic to the specified URL.
+    /// This will proxy all the traffic to the server through the specified URL.
+    /// HTTP, HTTPS and SOCKS5 proxies are supported. With `socks5h://`,
+    /// the server name is resolved by the proxy.
+    ///
+    /// The connections checked with the certificate fingerprint or known servers go through it too.
     /// ```
     /// client.proxy("https://10.1.1.100:8080");
+    /// client.proxy("socks5://10.1.1.100:1080");
     /// ```
     pub fn proxy(&mut self, s: &str) {
-        self.proxy = s.to_string();
+        self.proxy.url = s.to_string();
+        self.http_client = None;
+    }
+
+    /// Set the user and password to authenticate with the proxy.
+    ///
+    /// The password is redacted in the Debug output of the Client.
+    /// ```
+    /// client.proxy("https://10.1.1.100:8080");
+    /// client.proxy_auth("proxy_user", "proxy_pass");
+    /// ```
+    pub fn proxy_auth(&mut self, user: &str, password: &str) {
+        self.proxy.user = user.to_string();
+        self.proxy.password = password.to_string();
+        self.http_client = None;
+    }
+
+    /// Set the servers to connect to directly instead of through the proxy.
+    ///
+    /// This is a comma separated list, the same as the NO_PROXY environment variable.
+    /// An entry is an IP address, a subnet such as `10.0.0.0/8`, a host name
+    /// that also matches its subdomains such as `.example.com`, or `*` for every server.
+    /// ```
+    /// client.no_proxy("10.0.0.0/8, 192.168.1.10, .mgmt.example.com");
+    /// ```
+    pub fn no_proxy(&mut self, list: &str) {
+        self.proxy.set_no_proxy(list);
+        self.http_client = None;
+    }
+
+    /// Use the HTTPS_PROXY and NO_PROXY environment variables. Default is false.
+    ///
+    /// ALL_PROXY is used if HTTPS_PROXY is not set, and the lower case names work as well.
+    /// A proxy set with `Client::proxy()` is used instead of the environment variable,
+    /// and the servers in NO_PROXY are added to the ones set with `Client::no_proxy()`.
+    /// ```
+    /// client.proxy_from_env(true);
+    /// ```
+    pub fn proxy_from_env(&mut self, b: bool) {
+        self.proxy.from_env = b;
+        self.http_client = None;
+    }
+
+    /// Set the path of the API on the server. Default is web_api.
+    ///
+    /// Use gaia_api for the Gaia API, or the full path when the server is behind
+    /// a reverse proxy with a path prefix.
+    /// ```
+    /// client.api_path("gaia_api");
+    /// client.api_path("/checkpoint/web_api");
+    /// ```
+    pub fn api_path(&mut self, path: &str) {
+        self.api_path = request::api_path(path);
+    }
+
+    /// Pin the API version used in the URLs, such as /web_api/v1.5/show-hosts.
+    ///
+    /// The behavior of the commands differs between API versions.
+    /// By default, the latest version of the server is used.
+    /// ```
+    /// client.api_version(ApiVersion::new(1, 5));
+    /// ```
+    pub fn api_version(&mut self, version: ApiVersion) {
+        self.api_version = Some(version.to_string());
+    }
+
+    /// Pick the highest API version supported by the server between min and max, inclusive,
+    /// and pin it with `Client::api_version()`.
+    ///
+    /// This calls show-api-versions, so login first.
+    /// An error is returned if the server supports none of the versions.
+    /// ```
+    /// client.login("user", "pass")?;
+    /// let version = client.negotiate_api_version(ApiVersion::new(1, 5), ApiVersion::new(1, 8))?;
+    /// println!("using API version {}", version);
+    /// ```
+    pub fn negotiate_api_version(
+        &mut self,
+        min: ApiVersion,
+        max: ApiVersion
+        ) -> Result<ApiVersion>
+    {
+        let res = self.call("show-api-versions", json!({}))?;
+        let (version, name) = request::negotiate_api_version(&res, min, max)?;
+        self.api_version = Some(name);
+
+        Ok(version)
     }
 
-    /// Set the connection timeout in seconds to the Management server. Default is 30 seconds.
+    /// Set the timeout in seconds to connect to the Management server. Default is 30 seconds.
+    ///
+    /// This is also the timeout of the whole request unless `Client::request_timeout()` is set.
     /// ```
     /// client.connect_timeout(10);
     /// ```
     pub fn connect_timeout(&mut self, t: u64) {
         self.connect_timeout = time::Duration::from_secs(t);
+        self.http_client = None;
+    }
+
+    /// Set the timeout in seconds of a whole request, from connecting to reading the response.
+    /// Default is the `Client::connect_timeout()`.
+    ///
+    /// Use `Client::command_timeout()` for the commands that take longer.
+    /// ```
+    /// client.request_timeout(60);
+    /// ```
+    pub fn request_timeout(&mut self, t: u64) {
+        self.request_timeout = Some(time::Duration::from_secs(t));
+        self.http_client = None;
+    }
+
+    /// Set the timeout in seconds of the requests of a command,
+    /// instead of the `Client::request_timeout()`.
+    ///
+    /// The commands that wait for a task, such as publish and install-policy,
+    /// return a task-id quickly, so this is mostly needed when wait_for_task is false
+    /// or for a large query page.
+    /// ```
+    /// client.command_timeout("publish", 300);
+    /// client.command_timeout("show-objects", 120);
+    /// ```
+    pub fn command_timeout(&mut self, command: &str, t: u64) {
+        self.command_timeouts.insert(command.to_string(), time::Duration::from_secs(t));
     }
 
     /// Set the login session-timeout in seconds. Default is 600 seconds.
@@ -493,6 +1236,9 @@ impl Client {
     }
 
     /// Set to true to login with read only permissions. Default is false.
+    ///
+    /// Commands that change the configuration are also refused by the Client,
+    /// see `Client::mutating_command()`.
     /// ```
     /// client.read_only(true);
     /// ```
@@ -500,6 +1246,34 @@ impl Client {
         self.read_only = b;
     }
 
+    /// Set if a command changes the configuration.
+    ///
+    /// When the Client is read only, commands that change the configuration
+    /// return an `Error::ReadOnly` without being sent to the server.
+    /// The show-* commands and a few others such as where-used are known to be read only,
+    /// every other command is treated as changing the configuration.
+    /// ```
+    /// client.read_only(true);
+    /// client.mutating_command("run-script", false);
+    /// client.mutating_command("show-last-published-session", true);
+    /// ```
+    pub fn mutating_command(&mut self, command: &str, b: bool) {
+        self.mutating_commands.insert(command.to_string(), b);
+    }
+
+    // Check if a command changes the configuration, using the overrides first.
+    fn is_mutating(&self, command: &str) -> bool {
+        match self.mutating_commands.get(command) {
+            Some(t) => *t,
+            None => commands::is_mutating(command),
+        }
+    }
+
+    // Check if the Client refuses a command because it is read only.
+    pub(crate) fn is_refused(&self, command: &str) -> bool {
+        self.read_only && self.is_mutating(command)
+    }
+
     /// Set to true to continue the last session. Default is false.
     /// ```
     /// client.continue_last_session(true);
@@ -508,6 +1282,16 @@ impl Client {
         self.continue_last_session = b;
     }
 
+    /// Get the base URL the commands are sent to.
+    /// ```
+    /// let client = Client::new("fd00::10", 443);
+    /// assert_eq!("https://[fd00::10]:443/web_api", client.base_url());
+    /// ```
+    pub fn base_url(&self) -> String {
+        request::base_url(self.scheme.as_str(), self.server.as_str(), self.port,
+                          self.api_path.as_str(), self.api_version.as_deref())
+    }
+
     /// Get the sid after logging in.
     /// ```
     /// client.login("user", "pass")?;
@@ -535,6 +1319,18 @@ impl Client {
         self.api_server_version.as_str()
     }
 
+    /// Get the LoginInfo after logging in.
+    ///
+    /// This is None before logging in, after logging out and after resuming a session.
+    /// ```
+    /// client.login("user", "pass")?;
+    /// let info = client.login_info().unwrap();
+    /// println!("session-timeout: {}, api version: {}", info.session_timeout, info.api_version);
+    /// ```
+    pub fn login_info(&self) -> Option<&LoginInfo> {
+        self.login_info.as_ref()
+    }
+
     /// Wait for an API call to complete.
     ///
     /// Some API commands return a task-id or tasks while they continue to run.
@@ -559,55 +1355,27 @@ impl Client {
 
     // Wait for a task to complete that returned a task-id.
     fn _wait_for_task(&mut self, taskid: &str, command: &str) -> Result<Response> {
-        let mut _res = Response::new();
-
         loop {
-            _res = self.call("show-task", json!({"task-id": taskid, "details-level": "full"}))?;
-
-            let percent = match _res.data["tasks"][0].get("progress-percentage") {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("progress-percentage", json!(_res)))
-            };
-
-            let status = match _res.data["tasks"][0].get("status") {
-                Some(t) => t,
-                None => return Err(Error::InvalidResponse("status", json!(_res)))
-            };
+            let res = self.call("show-task", request::show_task_payload(taskid))?;
 
-            println!("{} {} - {}%", command, status, percent);
-
-            if status != "in progress" {
-                break;
+            if !request::task_in_progress(command, &res)? {
+                return Ok(res);
             }
 
             thread::sleep(time::Duration::from_secs(5));
         }
-
-        Ok(_res)
     }
 
     // Wait for multiple tasks to complete.
-    fn _wait_for_tasks(
-        &mut self,
-        tasks: &Vec<serde_json::Value>,
-        command: &str
-        ) -> Result<Response>
-    {
-        let mut _res = Response::new();
-        let mut ids = Vec::new();
-
-        for task in tasks {
-            if task.get("task-id").is_some() {
-                let id = task["task-id"].as_str().unwrap();
-                ids.push(id);
-                self._wait_for_task(id, command)?;
-            }
+    fn _wait_for_tasks(&mut self, ids: &[String], command: &str) -> Result<Response> {
+        for id in ids {
+            self._wait_for_task(id.as_str(), command)?;
         }
 
-        _res = self.call("show-task", json!({"task-id": ids, "details-level": "full"}))?;
-        Response::check_tasks_status(&mut _res);
+        let mut res = self.call("show-task", request::show_task_payload(ids))?;
+        Response::check_tasks_status(&mut res);
 
-        Ok(_res)
+        Ok(res)
     }
 
     /// Set the log file name that will contain the API calls.
@@ -620,103 +1388,291 @@ impl Client {
     /// client.log_file("/home/admin/log.txt");
     /// ```
     pub fn log_file(&mut self, s: &str) {
-        self.log_file = s.to_string();
+        self.log.set_file(s);
     }
 
-    // Update the vector of API calls
-    fn update_calls(
-        &mut self,
-        command: &str,
-        url: &str,
-        headers: HeaderMap,
-        mut payload: serde_json::Value,
-        res: &Response,
-        ) -> Result<()>
-    {
-        if command == "login" && self.show_password == false {
-            if let Some(obj) = payload.get_mut("password") {
-                *obj = json!("*****");
-            }
-            else {
-                let msg = String::from("Failed to get the password to obfuscate from payload");
-                return Err(Error::Custom(msg));
-            }
-        }
+    /// Save the API calls to a file.
+    ///
+    /// API calls made before log_file was set will not be saved.
+    ///
+    /// After the API calls are saved, the API calls and log_file will be cleared.
+    /// ```
+    /// client.log_file("/home/admin/log.txt");
+    /// client.call("show-host", json!({"name": "host1"}))?;
+    /// client.save_log()?;
+    /// ```
+    pub fn save_log(&mut self) -> Result<()> {
+        self.log.save()
+    }
 
-        let mut map = HashMap::new();
+    /// Show the login password or API key as clear text in the log file.
+    ///
+    /// This must be set before logging in or the password or API key will be obfuscated.
+    /// ```
+    /// client.log_file("/home/admin/log.txt");
+    /// client.show_password(true);
+    /// client.login("user", "pass")?;
+    /// client.save_log()?;
+    /// ```
+    pub fn show_password(&mut self, b: bool) {
+        self.log.set_show_password(b);
+    }
 
-        for (k, v) in headers.iter() {
-            let k = k.as_str().to_string();
-            let v = v.to_str()?;
-            let v = v.to_string();
+    /// Login again and retry the call once if the session expired. Default is false.
+    ///
+    /// The credentials from the last successful login are used,
+    /// or the credential_provider if one is set.
+    /// Only the commands that do not change the configuration are retried.
+    /// The unpublished changes of the expired session are lost, so a command such as
+    /// add-host or publish returns an error after the login instead.
+    /// The login is saved in the log file the same as any other call.
+    /// ```
+    /// client.auto_relogin(true);
+    /// client.login("user", "pass")?;
+    /// ```
+    pub fn auto_relogin(&mut self, b: bool) {
+        self.auto_relogin = b;
+    }
+
+    /// Retry the calls that fail with a transient error, such as a reset connection,
+    /// a timeout or a 5xx status. See `RetryPolicy`.
+    ///
+    /// Every failed attempt is saved in the log file.
+    /// ```
+    /// client.retry_policy(RetryPolicy::default());
+    /// ```
+    pub fn retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = Some(policy);
+    }
 
-            map.insert(k, v);
+    /// Limit the rate of the requests sent to the server.
+    ///
+    /// Up to burst requests are sent right away, then the calls wait so that no more than
+    /// requests_per_second are sent. Every request counts, including the show-task
+    /// requests while waiting for a task, each page of a query, each retry and the keepalive.
+    /// The Clients from `Client::login_to_domain()` share the same limit.
+    ///
+    /// A rate of 0 removes the limit. A negative, infinite or NaN rate,
+    /// or a burst of 0, is an error.
+    /// ```
+    /// client.rate_limit(5.0, 10)?;
+    /// ```
+    pub fn rate_limit(&mut self, requests_per_second: f64, burst: u32) -> Result<()> {
+        self.rate_limiter = if requests_per_second == 0.0 {
+            None
         }
+        else {
+            Some(Arc::new(RateLimiter::new(requests_per_second, burst)?))
+        };
 
-        let j = json!({
-            "Request": {
-                "headers": map,
-                "payload": payload,
-                "url": url
-            },
-            "Response": res
-        });
+        Ok(())
+    }
 
-        let mut v = vec!(j);
-        self.all_calls.append(&mut v);
+    /// Set the CredentialProvider used by `Client::login_with_provider()`
+    /// and to login again after the session expired.
+    ///
+    /// This is useful when the credentials should not be kept in the Client or may change.
+    /// ```
+    /// client.credential_provider(NetrcCredentials::new("/home/admin/.cp_api_netrc"));
+    /// client.auto_relogin(true);
+    /// client.login_with_provider()?;
+    /// ```
+    pub fn credential_provider<P>(&mut self, provider: P)
+        where P: CredentialProvider + 'static
+    {
+        self.credential_provider = Some(Box::new(provider));
+    }
 
-        Ok(())
+    /// Set the Transport that sends the commands instead of the default ReqwestTransport.
+    ///
+    /// The certificate, proxy and timeout settings are not used with another Transport.
+    /// This is useful to test code built on the Client without a management server.
+    /// ```
+    /// client.transport(|command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+    ///     Ok(TransportResponse::json(200, &recorded_response(command)))
+    /// });
+    /// ```
+    pub fn transport<T>(&mut self, transport: T)
+        where T: Transport + 'static
+    {
+        self.transport = Some(Arc::new(transport));
     }
 
-    /// Save the API calls to a file.
+    /// Get the number of times the Client logged in again after the session expired.
+    /// ```
+    /// println!("logged in again {} times", client.relogin_count());
+    /// ```
+    pub fn relogin_count(&self) -> u64 {
+        self.relogin_count
+    }
+
+    /// Set to false to keep the session when the Client is dropped. Default is true.
     ///
-    /// API calls made before log_file was set will not be saved.
+    /// This is used with `Client::save_session()` to resume the session in another process.
+    /// ```
+    /// client.logout_on_drop(false);
+    /// ```
+    pub fn logout_on_drop(&mut self, b: bool) {
+        self.logout_on_drop = b;
+    }
+
+    /// Save the session to a file so it can be resumed with `Client::resume_session()`.
     ///
-    /// After the API calls are saved, the API calls and log_file will be cleared.
+    /// The file contains the sid, so it is only readable by the owner on Unix.
+    /// Set logout_on_drop to false or the session will be logged out when the Client is dropped.
     /// ```
-    /// client.log_file("/home/admin/log.txt");
-    /// client.call("show-host", json!({"name": "host1"}))?;
-    /// client.save_log()?;
+    /// client.login("user", "pass")?;
+    /// client.logout_on_drop(false);
+    /// client.save_session("/home/admin/.cp_api_session")?;
     /// ```
-    pub fn save_log(&mut self) -> Result<()> {
-        if self.log_file.is_empty() {
-            let msg = String::from("log_file on the Client is not set");
+    pub fn save_session(&self, file: &str) -> Result<()> {
+        if self.sid.is_empty() {
+            let msg = String::from("There is no session to save, login first");
             return Err(Error::Custom(msg));
         }
 
-        let mut f = File::create(self.log_file.as_str())?;
+        let saved = SavedSession {
+            server: self.server.clone(),
+            port: self.port,
+            sid: self.sid.clone(),
+            uid: self.uid.clone(),
+            api_server_version: self.api_server_version.clone(),
+            domain: self.domain.clone(),
+        };
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut f = options.open(file)?;
 
-        // Save all_calls with an indent of 4 spaces instead of 2 (the default)
+        // Save with an indent of 4 spaces instead of 2 (the default)
         let buf = Vec::new();
         let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
         let mut ser = serde_json::Serializer::with_formatter(buf, formatter);
 
-        self.all_calls.serialize(&mut ser)?;
+        saved.serialize(&mut ser)?;
         f.write_all(&ser.into_inner())?;
 
-        self.all_calls.clear();
-        self.log_file.clear();
-
         Ok(())
     }
 
-    /// Show the login password as clear text in the log file.
+    /// Resume a session saved with `Client::save_session()`.
+    ///
+    /// The session is checked with show-session. If it is no longer valid,
+    /// a new login is done with the credential_provider, the same as
+    /// `Client::login_with_provider()`.
     ///
-    /// This must be set before logging in or the password will be obfuscated.
+    /// The Response from show-session or the new login is returned.
+    /// `Client::login_info()` is only set after a new login.
+    /// ```
+    /// let mut client = Client::new("192.168.1.10", 443);
+    /// client.certificate("/home/admin/cert.cer");
+    /// client.credential_provider(EnvCredentials::new());
+    /// client.resume_session("/home/admin/.cp_api_session")?;
+    /// ```
+    pub fn resume_session(&mut self, file: &str) -> Result<Response> {
+        let saved: SavedSession = serde_json::from_str(&fs::read_to_string(file)?)?;
+
+        if saved.server != self.server || saved.port != self.port {
+            let msg = format!("The saved session is for {}:{}, not {}:{}",
+                              saved.server, saved.port, self.server, self.port);
+            return Err(Error::Custom(msg));
+        }
+
+        self.domain = saved.domain;
+        self.sid = saved.sid;
+        self.uid = saved.uid;
+        self.api_server_version = saved.api_server_version;
+
+        let show_session = self.call("show-session", json!({}))?;
+        if show_session.is_success() {
+            return Ok(show_session);
+        }
+
+        self.sid.clear();
+        self.uid.clear();
+        self.api_server_version.clear();
+
+        self.login_with_provider()
+    }
+
+    /// Start sending the keepalive command in the background while the Client is idle.
+    ///
+    /// A keepalive is sent when no call was made for the fraction of the session_timeout,
+    /// for example a fraction of 0.5 with the default session_timeout sends a keepalive
+    /// after 300 seconds of being idle.
+    ///
+    /// The keepalive stops on logout, when the Client is dropped
+    /// or with `Client::stop_keepalive()`. Keepalives are not saved in the log file.
     /// ```
-    /// client.log_file("/home/admin/log.txt");
-    /// client.show_password(true);
     /// client.login("user", "pass")?;
-    /// client.save_log()?;
+    /// let keepalive = client.start_keepalive(0.5)?;
+    /// // ...
+    /// if let Some(e) = keepalive.last_error() {
+    ///     eprintln!("keepalive failed: {}", e);
+    /// }
     /// ```
-    pub fn show_password(&mut self, b: bool) {
-        self.show_password = b;
+    pub fn start_keepalive(&mut self, fraction: f64) -> Result<KeepaliveHandle> {
+        if self.sid.is_empty() {
+            let msg = String::from("Login before starting the keepalive");
+            return Err(Error::Custom(msg));
+        }
+
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            let msg = format!("The keepalive fraction must be greater than 0 and at most 1, got {}",
+                              fraction);
+            return Err(Error::Custom(msg));
+        }
+
+        self.stop_keepalive();
+
+        let headers = request::headers("")?;
+        let transport = self.current_transport("keepalive")?;
+        let millis = self.session_timeout as f64 * fraction * 1000.0;
+        let interval = time::Duration::from_millis(millis as u64);
+
+        let keepalive = Keepalive::start(transport, headers, self.sid.as_str(), interval,
+                                         self.rate_limiter.clone());
+        let handle = keepalive.handle();
+        self.keepalive = Some(keepalive);
+
+        Ok(handle)
+    }
+
+    /// Stop sending the keepalive command in the background.
+    /// ```
+    /// client.stop_keepalive();
+    /// ```
+    pub fn stop_keepalive(&mut self) {
+        if let Some(mut keepalive) = self.keepalive.take() {
+            keepalive.stop();
+        }
     }
 }
 
+// The session values saved to a file.
+#[derive(Serialize, Deserialize)]
+struct SavedSession {
+    server: String,
+    port: u16,
+    sid: String,
+    uid: String,
+    #[serde(rename = "api-server-version")]
+    api_server_version: String,
+    domain: String,
+}
+
 impl Drop for Client {
     fn drop(&mut self) {
-        if !self.sid.is_empty() {
+        self.stop_keepalive();
+
+        if self.logout_on_drop && !self.sid.is_empty() {
             if let Err(e) = self.logout() {
                 // A panic isn't ideal since the Client is being dropped and can't be used anymore.
                 // Printing an error message isn't ideal either.
@@ -732,20 +1688,38 @@ impl fmt::Debug for Client {
         f.debug_struct("Client")
             .field("server", &self.server)
             .field("port", &self.port)
+            .field("scheme", &self.scheme)
+            .field("api_path", &self.api_path)
+            .field("api_version", &self.api_version)
             .field("certificate", &self.certificate)
+            .field("certificate_fingerprint", &self.certificate_fingerprint)
+            .field("known_servers", &self.known_servers)
+            .field("trust_on_first_use", &self.trust_on_first_use)
+            .field("identity", &self.identity)
             .field("accept_invalid_certs", &self.accept_invalid_certs)
             .field("proxy", &self.proxy)
             .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("command_timeouts", &self.command_timeouts)
             .field("session_timeout", &self.session_timeout)
             .field("domain", &self.domain)
             .field("read_only", &self.read_only)
+            .field("mutating_commands", &self.mutating_commands)
             .field("continue_last_session", &self.continue_last_session)
             .field("sid", &self.sid)
             .field("uid", &self.uid)
             .field("api_server_version", &self.api_server_version)
+            .field("login_info", &self.login_info)
             .field("wait_for_task", &self.wait_for_task)
-            .field("log_file", &self.log_file)
-            .field("show_password", &self.show_password)
+            .field("log_file", &self.log.file())
+            .field("show_password", &self.log.show_password())
+            .field("auto_relogin", &self.auto_relogin)
+            .field("retry_policy", &self.retry_policy)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("relogin_count", &self.relogin_count)
+            .field("keepalive", &self.keepalive.is_some())
+            .field("custom_transport", &self.transport.is_some())
+            .field("logout_on_drop", &self.logout_on_drop)
             .finish()
     }
 }
@@ -0,0 +1,18 @@
+// Commands that do not change the configuration, other than the show-* commands.
+const NON_MUTATING: &[&str] = &[
+    "login",
+    "logout",
+    "keepalive",
+    "login-to-domain",
+    "switch-session",
+    "where-used",
+    "verify-policy",
+    "get-interfaces",
+];
+
+// Check if a command changes the configuration.
+//
+// Commands that are not known to be read only are treated as changing the configuration.
+pub(crate) fn is_mutating(command: &str) -> bool {
+    !(command.starts_with("show-") || NON_MUTATING.contains(&command))
+}
@@ -0,0 +1,336 @@
+use std::{env, fmt, fs};
+
+use serde_json::json;
+
+use crate::error::{Error, Result};
+
+/// Credentials used to login to the API.
+///
+/// ```
+/// let credentials = Credentials::password("user", "pass");
+/// let credentials = Credentials::api_key("eXaMpLeKeY123==");
+/// ```
+#[derive(Clone)]
+pub enum Credentials {
+    /// A user and password.
+    Password {
+        user: String,
+        password: String,
+    },
+
+    /// An API key.
+    ApiKey(String),
+
+    /// Login as root on the management server, see `Client::login_as_root()`.
+    Root,
+}
+
+impl Credentials {
+    /// Create user and password Credentials.
+    pub fn password(user: &str, password: &str) -> Self {
+        Credentials::Password {
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    /// Create API key Credentials.
+    pub fn api_key(api_key: &str) -> Self {
+        Credentials::ApiKey(api_key.to_string())
+    }
+
+    // Build the part of the login payload that holds the credentials.
+    pub(crate) fn payload(&self) -> serde_json::Value {
+        match self {
+            Credentials::Password { user, password } => json!({
+                "user": user,
+                "password": password,
+            }),
+            Credentials::ApiKey(api_key) => json!({
+                "api-key": api_key,
+            }),
+            Credentials::Root => json!({}),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Credentials::Password { user, .. } => {
+                f.debug_struct("Password")
+                    .field("user", user)
+                    .field("password", &"*****")
+                    .finish()
+            },
+            Credentials::ApiKey(_) => {
+                f.debug_tuple("ApiKey")
+                    .field(&"*****")
+                    .finish()
+            },
+            Credentials::Root => f.write_str("Root"),
+        }
+    }
+}
+
+/// A source of Credentials to login with.
+///
+/// The Client uses a CredentialProvider with `Client::login_with_provider()`
+/// and to login again after the session expires.
+///
+/// Credentials implement CredentialProvider to always provide the same value,
+/// and so does any closure taking the server and Domain.
+///
+/// ```
+/// client.credential_provider(EnvCredentials::new());
+/// client.credential_provider(NetrcCredentials::new("/home/admin/.cp_api_netrc"));
+/// client.credential_provider(Credentials::api_key("eXaMpLeKeY123=="));
+/// client.credential_provider(|server: &str, _domain: &str| {
+///     Ok(Credentials::api_key(&read_key_from_vault(server)?))
+/// });
+/// ```
+pub trait CredentialProvider: Send {
+    /// Get the Credentials to login to the server and Domain.
+    ///
+    /// The Domain is empty if the Client does not have a Domain set.
+    fn credentials(&self, server: &str, domain: &str) -> Result<Credentials>;
+}
+
+impl CredentialProvider for Credentials {
+    fn credentials(&self, _server: &str, _domain: &str) -> Result<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+impl<F> CredentialProvider for F
+    where F: Fn(&str, &str) -> Result<Credentials> + Send
+{
+    fn credentials(&self, server: &str, domain: &str) -> Result<Credentials> {
+        self(server, domain)
+    }
+}
+
+/// Read Credentials from environment variables.
+///
+/// An API key is used if `CP_API_KEY` is set,
+/// otherwise the user and password are read from `CP_API_USER` and `CP_API_PASSWORD`.
+///
+/// ```
+/// // CP_API_KEY, or CP_API_USER and CP_API_PASSWORD
+/// client.credential_provider(EnvCredentials::new());
+///
+/// // MYAPP_KEY, or MYAPP_USER and MYAPP_PASSWORD
+/// client.credential_provider(EnvCredentials::with_prefix("MYAPP"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct EnvCredentials {
+    prefix: String,
+}
+
+impl EnvCredentials {
+    /// Read the variables starting with `CP_API`.
+    pub fn new() -> Self {
+        EnvCredentials::with_prefix("CP_API")
+    }
+
+    /// Read the variables starting with a different prefix.
+    pub fn with_prefix(prefix: &str) -> Self {
+        EnvCredentials {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<String> {
+        env::var(format!("{}_{}", self.prefix, name)).ok()
+    }
+}
+
+impl Default for EnvCredentials {
+    fn default() -> Self {
+        EnvCredentials::new()
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&self, _server: &str, _domain: &str) -> Result<Credentials> {
+        if let Some(api_key) = self.var("KEY") {
+            return Ok(Credentials::ApiKey(api_key));
+        }
+
+        match (self.var("USER"), self.var("PASSWORD")) {
+            (Some(user), Some(password)) => Ok(Credentials::Password { user, password }),
+            _ => {
+                let msg = format!("Set {0}_KEY, or {0}_USER and {0}_PASSWORD to login",
+                                  self.prefix);
+                Err(Error::Custom(msg))
+            }
+        }
+    }
+}
+
+/// Read Credentials from a netrc style file.
+///
+/// Each entry starts with `machine` followed by the server, or `default`
+/// to match any server. An entry can have a `domain` and either
+/// a `login` and `password`, or an `api-key`. Values with spaces are quoted.
+///
+/// An entry for the server and Domain is used first, then an entry for the server
+/// without a Domain, and last the `default` entry.
+///
+/// ```text
+/// machine 192.168.1.10 login admin password vpn123
+/// machine 192.168.1.20 domain "Domain A" api-key eXaMpLeKeY123==
+/// default login readonly password abc123
+/// ```
+///
+/// ```
+/// client.credential_provider(NetrcCredentials::new("/home/admin/.cp_api_netrc"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct NetrcCredentials {
+    file: String,
+}
+
+#[derive(Default)]
+struct NetrcEntry {
+    machine: Option<String>,
+    domain: String,
+    login: Option<String>,
+    password: Option<String>,
+    api_key: Option<String>,
+}
+
+impl NetrcCredentials {
+    /// Read the Credentials from a file.
+    pub fn new(file: &str) -> Self {
+        NetrcCredentials {
+            file: file.to_string(),
+        }
+    }
+
+    // Parse the entries in the file.
+    fn entries(&self) -> Result<Vec<NetrcEntry>> {
+        let contents = fs::read_to_string(self.file.as_str())?;
+        let tokens = tokenize(contents.as_str());
+        let mut tokens = tokens.into_iter();
+
+        let mut entries = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "machine" => entries.push(NetrcEntry {
+                    machine: Some(self.value(&mut tokens, "machine")?),
+                    ..Default::default()
+                }),
+                "default" => entries.push(NetrcEntry::default()),
+                key => {
+                    let value = self.value(&mut tokens, key)?;
+
+                    let entry = match entries.last_mut() {
+                        Some(t) => t,
+                        None => {
+                            let msg = format!("'{}' in {} must come after machine or default",
+                                              key, self.file);
+                            return Err(Error::Custom(msg));
+                        }
+                    };
+
+                    match key {
+                        "domain" => entry.domain = value,
+                        "login" => entry.login = Some(value),
+                        "password" => entry.password = Some(value),
+                        "api-key" => entry.api_key = Some(value),
+                        _ => {
+                            let msg = format!("Unknown key '{}' in {}", key, self.file);
+                            return Err(Error::Custom(msg));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn value<I: Iterator<Item = String>>(&self, tokens: &mut I, key: &str) -> Result<String> {
+        match tokens.next() {
+            Some(t) => Ok(t),
+            None => {
+                let msg = format!("Missing the value for '{}' in {}", key, self.file);
+                Err(Error::Custom(msg))
+            }
+        }
+    }
+}
+
+impl CredentialProvider for NetrcCredentials {
+    fn credentials(&self, server: &str, domain: &str) -> Result<Credentials> {
+        let entries = self.entries()?;
+
+        let is_server = |e: &&NetrcEntry| e.machine.as_ref().is_some_and(|m| m == server);
+
+        let entry = entries.iter().find(|e| is_server(e) && e.domain == domain)
+            .or_else(|| entries.iter().find(|e| is_server(e) && e.domain.is_empty()))
+            .or_else(|| entries.iter().find(|e| e.machine.is_none()));
+
+        let entry = match entry {
+            Some(t) => t,
+            None => {
+                let msg = format!("No entry for server '{}' and domain '{}' in {}",
+                                  server, domain, self.file);
+                return Err(Error::Custom(msg));
+            }
+        };
+
+        if let Some(ref api_key) = entry.api_key {
+            return Ok(Credentials::ApiKey(api_key.clone()));
+        }
+
+        match (&entry.login, &entry.password) {
+            (Some(user), Some(password)) => Ok(Credentials::password(user, password)),
+            _ => {
+                let msg = format!("The entry for server '{}' in {} needs a login and password \
+                                   or an api-key", server, self.file);
+                Err(Error::Custom(msg))
+            }
+        }
+    }
+}
+
+// Split the file into whitespace separated tokens, keeping quoted values together.
+// Lines starting with # are comments.
+fn tokenize(contents: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    for line in contents.lines() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut chars = line.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            }
+            else if c == '"' {
+                chars.next();
+                let token: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(token);
+            }
+            else {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    tokens
+}
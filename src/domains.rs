@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::client::Client;
+use crate::response::Response;
+use crate::error::{Error, Result};
+
+/// Manage sessions to multiple Domains on a Multi-Domain Server.
+///
+/// The DomainManager is created from a Client logged into the Multi-Domain Server.
+/// A Domain session is created with `Client::login_to_domain()` the first time
+/// the Domain is used and reused after that.
+///
+/// Every session is logged out with `DomainManager::shutdown()`,
+/// or when the DomainManager is dropped, the Domains first and then the Multi-Domain Server.
+/// A session is not logged out on drop if `Client::logout_on_drop()` was set to false.
+///
+/// ```
+/// let mut mds = Client::new("192.168.1.10", 443);
+/// mds.login("user", "pass")?;
+///
+/// let mut domains = DomainManager::new(mds);
+/// domains.call("Domain A", "add-host", json!({"name": "host1", "ip-address": "10.1.1.1"}))?;
+/// domains.call("Domain B", "add-host", json!({"name": "host1", "ip-address": "10.2.1.1"}))?;
+/// let hosts = domains.query("Domain A", "show-hosts", json!({"details-level": "standard"}))?;
+///
+/// domains.shutdown()?;
+/// ```
+#[derive(Debug)]
+pub struct DomainManager {
+    mds: Client,
+    domains: HashMap<String, Client>,
+}
+
+impl DomainManager {
+    /// Create a new DomainManager from a Client logged into the Multi-Domain Server.
+    pub fn new(mds: Client) -> Self {
+        DomainManager {
+            mds,
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Get the Client logged into the Multi-Domain Server.
+    pub fn mds(&mut self) -> &mut Client {
+        &mut self.mds
+    }
+
+    /// Get the Client for a Domain, logging into the Domain if needed.
+    /// ```
+    /// let domain_a = domains.client("Domain A")?;
+    /// println!("sid: {}", domain_a.sid());
+    /// ```
+    pub fn client(&mut self, domain: &str) -> Result<&mut Client> {
+        if !self.domains.contains_key(domain) {
+            let client = self.mds.login_to_domain(domain)?;
+            self.domains.insert(domain.to_string(), client);
+        }
+
+        match self.domains.get_mut(domain) {
+            Some(t) => Ok(t),
+            None => {
+                let msg = format!("Failed to get the session for domain '{}'", domain);
+                Err(Error::Custom(msg))
+            }
+        }
+    }
+
+    /// Get the names of the Domains that are logged into.
+    pub fn domains(&self) -> Vec<&str> {
+        self.domains.keys().map(|k| k.as_str()).collect()
+    }
+
+    /// Perform an API call in a Domain.
+    ///
+    /// See `Client::call()`.
+    pub fn call(
+        &mut self,
+        domain: &str,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        self.client(domain)?.call(command, payload)
+    }
+
+    /// Perform an API call in a Domain and check that it was successful.
+    ///
+    /// See `Client::call_and_check()`.
+    pub fn call_and_check(
+        &mut self,
+        domain: &str,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        self.client(domain)?.call_and_check(command, payload)
+    }
+
+    /// Perform an API query in a Domain.
+    ///
+    /// See `Client::query()`.
+    pub fn query(
+        &mut self,
+        domain: &str,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        self.client(domain)?.query(command, payload)
+    }
+
+    /// Perform an API query in a Domain and check that it was successful.
+    ///
+    /// See `Client::query_and_check()`.
+    pub fn query_and_check(
+        &mut self,
+        domain: &str,
+        command: &str,
+        payload: serde_json::Value
+        ) -> Result<Response>
+    {
+        self.client(domain)?.query_and_check(command, payload)
+    }
+
+    /// Logout of a single Domain.
+    ///
+    /// Nothing is done if the Domain is not logged into.
+    pub fn logout(&mut self, domain: &str) -> Result<()> {
+        if let Some(mut client) = self.domains.remove(domain) {
+            let logout = client.logout()?;
+
+            if logout.is_not_success() {
+                let msg = format!("Failed to logout of domain '{}': {}",
+                                  domain, logout.data["message"]);
+                return Err(Error::Custom(msg));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logout of every Domain and then the Multi-Domain Server.
+    ///
+    /// All the sessions are logged out even if one fails,
+    /// and the failures are returned together in the error.
+    pub fn shutdown(mut self) -> Result<()> {
+        let mut failed = Vec::new();
+
+        let names: Vec<String> = self.domains.keys().cloned().collect();
+        for name in names {
+            if let Err(e) = self.logout(name.as_str()) {
+                failed.push(e.to_string());
+            }
+        }
+
+        match self.mds.logout() {
+            Ok(ref t) if t.is_not_success() => {
+                failed.push(format!("Failed to logout of the Multi-Domain Server: {}",
+                                    t.data["message"]));
+            },
+            Ok(_) => (),
+            Err(e) => failed.push(e.to_string()),
+        }
+
+        if !failed.is_empty() {
+            return Err(Error::Custom(failed.join("; ")));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for DomainManager {
+    fn drop(&mut self) {
+        // Each Client logs out when it is dropped. The fields are dropped in order,
+        // so drop the Domains first to log out of them before the Multi-Domain Server.
+        self.domains.clear();
+    }
+}
@@ -29,6 +29,23 @@ pub enum Error {
     /// Occurs when parsing a Response that does not contain the expected fields.
     InvalidResponse(&'static str, serde_json::Value),
 
+    /// Occurs when calling a command that changes the configuration with a read only Client.
+    /// Contains the command.
+    ReadOnly(String),
+
+    /// Occurs when the certificate of the server does not match the pinned fingerprint.
+    /// Contains the expected and the observed SHA-256 fingerprints.
+    FingerprintMismatch(String, String),
+
+    /// Occurs when the certificate of a server in the known servers file changed.
+    /// Contains the server, the known fingerprint and the new fingerprint.
+    KnownServerChanged(String, String, String),
+
+    /// Occurs when a server is not in the known servers file
+    /// and `Client::trust_on_first_use()` is not set.
+    /// Contains the server and the fingerprint of its certificate.
+    UnknownServer(String, String),
+
     /// Custom error message.
     Custom(String),
 }
@@ -46,12 +63,30 @@ impl fmt::Display for Error {
             InvalidResponse(ref s, ref r) => {
                 write!(f, "Failed to parse expected \"{}\" field from Response: {}", s, r)
             },
+            ReadOnly(ref s) => {
+                write!(f, "\"{}\" changes the configuration and the Client is read only", s)
+            },
+            FingerprintMismatch(ref expected, ref observed) => {
+                write!(f, "The server certificate fingerprint {} does not match \
+                           the pinned fingerprint {}", observed, expected)
+            },
+            KnownServerChanged(ref server, ref known, ref new) => {
+                write!(f, "The certificate of {} changed! Known fingerprint: {}, \
+                           new fingerprint: {}. If the change is expected, remove {} \
+                           from the known servers file", server, known, new, server)
+            },
+            UnknownServer(ref server, ref fingerprint) => {
+                write!(f, "{} is not in the known servers file. Check its certificate \
+                           fingerprint is {}, then add the line \"{} {}\" to the file \
+                           or trust it on first use", server, fingerprint, server, fingerprint)
+            },
             Custom(ref s) => write!(f, "{}", s),
         }
     }
 }
 
 impl StdError for Error {
+    #[allow(deprecated)]
     fn description(&self) -> &str {
         use self::Error::*;
         match self {
@@ -62,6 +97,10 @@ impl StdError for Error {
             Io(ref e) => e.description(),
             ParseInt(ref e) => e.description(),
             InvalidResponse(_, _) => "Failed to parse expected field from Response",
+            ReadOnly(_) => "Command changes the configuration and the Client is read only",
+            FingerprintMismatch(_, _) => "Server certificate does not match the pinned fingerprint",
+            KnownServerChanged(_, _, _) => "The certificate of a known server changed",
+            UnknownServer(_, _) => "The server is not in the known servers file",
             Custom(_) => "Custom error message",
         }
     }
@@ -76,6 +115,10 @@ impl StdError for Error {
             Io(ref e) => e.source(),
             ParseInt(ref e) => e.source(),
             InvalidResponse(_, _) |
+            ReadOnly(_) |
+            FingerprintMismatch(_, _) |
+            KnownServerChanged(_, _, _) |
+            UnknownServer(_, _) |
             Custom(_) => None,
         }
     }
@@ -0,0 +1,222 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::json;
+
+use crate::rate_limit::RateLimiter;
+use crate::transport::Transport;
+
+/// A handle to the background keepalive started with `Client::start_keepalive()`.
+///
+/// The handle can be kept after the keepalive stops to check how it went.
+///
+/// ```
+/// let keepalive = client.start_keepalive(0.5)?;
+/// // ...
+/// if let Some(e) = keepalive.last_error() {
+///     eprintln!("keepalive failed: {}", e);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeepaliveHandle {
+    shared: Arc<Shared>,
+}
+
+impl KeepaliveHandle {
+    /// Get the last error that occurred while sending a keepalive.
+    ///
+    /// This is not cleared by a later successful keepalive.
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.lock().last_error.clone()
+    }
+
+    /// Get the number of successful keepalives sent.
+    pub fn count(&self) -> u64 {
+        self.shared.lock().count
+    }
+
+    /// Check if the keepalive is still running.
+    pub fn is_running(&self) -> bool {
+        !self.shared.lock().stop
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    sid: String,
+    last_activity: Instant,
+    last_error: Option<String>,
+    count: u64,
+    stop: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A poisoned lock only means the keepalive thread panicked, the state is still usable.
+        match self.state.lock() {
+            Ok(t) => t,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+// The keepalive thread owned by the Client.
+pub(crate) struct Keepalive {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Keepalive {
+    // Start the keepalive thread.
+    //
+    // A keepalive is sent when no call was made during the interval,
+    // taking a token from the rate limiter of the Client.
+    pub(crate) fn start(
+        transport: Arc<dyn Transport>,
+        headers: HeaderMap,
+        sid: &str,
+        interval: Duration,
+        limiter: Option<Arc<RateLimiter>>
+        ) -> Keepalive
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                sid: sid.to_string(),
+                last_activity: Instant::now(),
+                last_error: None,
+                count: 0,
+                stop: false,
+            }),
+            cond: Condvar::new(),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || {
+            run(&thread_shared, transport.as_ref(), &headers, interval, limiter)
+        });
+
+        Keepalive {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub(crate) fn handle(&self) -> KeepaliveHandle {
+        KeepaliveHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    // Record that a call was made so the keepalive is not needed yet.
+    pub(crate) fn touch(&self) {
+        self.shared.lock().last_activity = Instant::now();
+    }
+
+    // Update the sid after logging in again.
+    pub(crate) fn set_sid(&self, sid: &str) {
+        self.shared.lock().sid = sid.to_string();
+    }
+
+    // Stop the keepalive thread and wait for it to finish.
+    pub(crate) fn stop(&mut self) {
+        self.shared.lock().stop = true;
+        self.shared.cond.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run(
+    shared: &Shared,
+    transport: &dyn Transport,
+    headers: &HeaderMap,
+    interval: Duration,
+    limiter: Option<Arc<RateLimiter>>
+    )
+{
+    let mut state = shared.lock();
+
+    loop {
+        if state.stop {
+            return;
+        }
+
+        let idle = state.last_activity.elapsed();
+        if idle < interval {
+            state = match shared.cond.wait_timeout(state, interval - idle) {
+                Ok(t) => t.0,
+                Err(e) => e.into_inner().0,
+            };
+            continue;
+        }
+
+        state.last_activity = Instant::now();
+
+        // Wait for the rate limit on the condition variable, so stopping is not delayed.
+        if let Some(ref limiter) = limiter {
+            let ready = Instant::now() + limiter.reserve();
+            loop {
+                let now = Instant::now();
+                if state.stop || now >= ready {
+                    break;
+                }
+
+                state = match shared.cond.wait_timeout(state, ready - now) {
+                    Ok(t) => t.0,
+                    Err(e) => e.into_inner().0,
+                };
+            }
+
+            if state.stop {
+                return;
+            }
+        }
+
+        let sid = state.sid.clone();
+        drop(state);
+
+        let result = send(transport, headers, &sid);
+
+        state = shared.lock();
+        match result {
+            Ok(_) => state.count += 1,
+            Err(e) => state.last_error = Some(e),
+        }
+    }
+}
+
+// Send a single keepalive.
+fn send(transport: &dyn Transport, headers: &HeaderMap, sid: &str) -> Result<(), String> {
+    let v = HeaderValue::from_str(sid).map_err(|e| e.to_string())?;
+
+    let mut headers = headers.clone();
+    headers.insert(HeaderName::from_static("x-chkp-sid"), v);
+
+    let res = transport.send("keepalive", &headers, &json!({}))
+        .map_err(|e| e.to_string())?;
+
+    if !(200..300).contains(&res.status) {
+        let data: serde_json::Value = serde_json::from_slice(&res.body)
+            .unwrap_or_else(|_| json!({}));
+        return Err(format!("keepalive was not successful. status: {}, code: {}, message: {}",
+                           res.status, data["code"], data["message"]));
+    }
+
+    Ok(())
+}
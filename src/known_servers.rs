@@ -0,0 +1,57 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+use crate::tls;
+use crate::error::{Error, Result};
+
+// Check the fingerprint of a server certificate against the known servers file.
+//
+// Each line of the file has a server and the SHA-256 fingerprint of its certificate,
+// such as `192.168.1.10:443 82:00:F1:...`. Lines starting with # are comments.
+//
+// The fingerprint of a known server must match or `Error::KnownServerChanged` is returned.
+// A server that is not in the file is added to it when trust_new is true,
+// otherwise `Error::UnknownServer` is returned with the observed fingerprint.
+pub(crate) fn check(file: &str, name: &str, observed: &str, trust_new: bool) -> Result<()> {
+    match known_fingerprint(file, name)? {
+        Some(ref known) if known == observed => Ok(()),
+        Some(known) => {
+            Err(Error::KnownServerChanged(name.to_string(), known, observed.to_string()))
+        },
+        None if trust_new => add(file, name, observed),
+        None => Err(Error::UnknownServer(name.to_string(), observed.to_string())),
+    }
+}
+
+// Get the fingerprint of a server from the file, if it is known.
+fn known_fingerprint(file: &str, name: &str) -> Result<Option<String>> {
+    let contents = match fs::read_to_string(file) {
+        Ok(t) => t,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Io(e)),
+    };
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        if let (Some(n), Some(fingerprint)) = (parts.next(), parts.next()) {
+            if n == name {
+                return tls::normalize_fingerprint(fingerprint).map(Some);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// Add a server to the file, creating the file if needed.
+fn add(file: &str, name: &str, fingerprint: &str) -> Result<()> {
+    let mut f = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(f, "{} {}", name, fingerprint)?;
+
+    Ok(())
+}
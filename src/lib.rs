@@ -60,9 +60,40 @@
 //! [ref]: https://sc1.checkpoint.com/documents/latest/APIs/index.html
 
 pub use crate::client::Client;
+#[cfg(feature = "async")]
+pub use crate::async_client::AsyncClient;
+pub use crate::credentials::{CredentialProvider, Credentials, EnvCredentials, NetrcCredentials};
+pub use crate::domains::DomainManager;
+pub use crate::keepalive::KeepaliveHandle;
+pub use crate::login_info::{LoginInfo, Timestamp};
 pub use crate::response::Response;
+pub use crate::retry::RetryPolicy;
+pub use crate::session::Session;
+pub use crate::sessions::{CleanupPolicy, CleanupReport, SessionInfo};
+pub use crate::transport::{ReqwestTransport, Transport, TransportResponse, TransportStream};
+pub use crate::version::ApiVersion;
 pub use crate::error::{Error, Result};
 
+#[cfg(feature = "async")]
+mod async_client;
+mod call_log;
 mod client;
+mod commands;
+mod credentials;
+mod domains;
+mod keepalive;
+mod known_servers;
+mod login_info;
+mod pinned;
+mod proxy;
+mod rate_limit;
+mod request;
 mod response;
+mod retry;
+mod session;
+mod sessions;
+mod stream;
+mod tls;
+mod transport;
+mod version;
 mod error;
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use serde_json::json;
+
+use crate::response::Response;
+use crate::version::ApiVersion;
+use crate::error::{Error, Result};
+
+/// Information about the session returned from a successful login.
+///
+/// ```
+/// client.login("user", "pass")?;
+/// let info = client.login_info().unwrap();
+///
+/// if info.standby {
+///     eprintln!("Warning: {} is a standby server", info.url);
+/// }
+/// if let Some(ref msg) = info.disk_space_message {
+///     eprintln!("Warning: {}", msg);
+/// }
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct LoginInfo {
+    /// The session-timeout in seconds.
+    pub session_timeout: u64,
+
+    /// The time of the previous login of this user, if there was one.
+    pub last_login_was_at: Option<Timestamp>,
+
+    /// True if the session is read only.
+    pub read_only: bool,
+
+    /// True if the server is a standby server.
+    pub standby: bool,
+
+    /// A warning about low disk space on the server.
+    pub disk_space_message: Option<String>,
+
+    /// The URL that was used to login.
+    pub url: String,
+
+    /// The api-server-version of the server.
+    pub api_version: ApiVersion,
+}
+
+/// A time returned from the API.
+#[derive(Clone, Debug, Serialize)]
+pub struct Timestamp {
+    /// Milliseconds since the Unix epoch.
+    pub posix: u64,
+
+    /// The time in ISO 8601 format.
+    pub iso_8601: String,
+}
+
+impl Timestamp {
+    // Parse a time object, such as last-login-was-at.
+    pub(crate) fn from_value(value: &serde_json::Value) -> Option<Timestamp> {
+        Some(Timestamp {
+            posix: value["posix"].as_u64()?,
+            iso_8601: value["iso-8601"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+impl LoginInfo {
+    // Parse the LoginInfo from a successful login Response.
+    pub(crate) fn from_response(login: &Response) -> Result<LoginInfo> {
+        let session_timeout = match login.data["session-timeout"].as_u64() {
+            Some(t) => t,
+            None => return Err(Error::InvalidResponse("session-timeout", json!(login)))
+        };
+
+        let api_version = match login.data["api-server-version"].as_str() {
+            Some(t) => t.parse()?,
+            None => return Err(Error::InvalidResponse("api-server-version", json!(login)))
+        };
+
+        Ok(LoginInfo {
+            session_timeout,
+            last_login_was_at: Timestamp::from_value(&login.data["last-login-was-at"]),
+            read_only: login.data["read-only"].as_bool().unwrap_or(false),
+            standby: login.data["standby"].as_bool().unwrap_or(false),
+            disk_space_message: login.data["disk-space-message"].as_str().map(|t| t.to_string()),
+            url: login.data["url"].as_str().unwrap_or_default().to_string(),
+            api_version,
+        })
+    }
+}
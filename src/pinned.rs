@@ -0,0 +1,600 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use native_tls::TlsStream;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::known_servers;
+use crate::proxy::ProxySettings;
+use crate::request;
+use crate::tls::{self, ClientIdentity};
+use crate::transport::{Transport, TransportResponse, TransportStream};
+use crate::error::{Error, Result};
+
+// How the certificate of the server is checked instead of the certificate chain.
+#[derive(Clone, Debug)]
+pub(crate) enum Pin {
+    // The SHA-256 fingerprint of the certificate, normalized.
+    Fingerprint(String),
+
+    // The fingerprint of the server in the known servers file.
+    KnownServers {
+        file: String,
+        name: String,
+        trust_new: bool,
+    },
+}
+
+impl Pin {
+    pub(crate) fn fingerprint(fingerprint: &str) -> Result<Pin> {
+        Ok(Pin::Fingerprint(tls::normalize_fingerprint(fingerprint)?))
+    }
+
+    pub(crate) fn known_servers(file: &str, server: &str, port: u16, trust_new: bool) -> Pin {
+        Pin::KnownServers {
+            file: file.to_string(),
+            name: format!("{}:{}", request::host(server), port),
+            trust_new,
+        }
+    }
+
+    // Check the fingerprint of the certificate a connection was set up with.
+    fn check(&self, observed: &str) -> Result<()> {
+        match self {
+            Pin::Fingerprint(expected) if expected == observed => Ok(()),
+            Pin::Fingerprint(expected) => {
+                Err(Error::FingerprintMismatch(expected.clone(), observed.to_string()))
+            },
+            Pin::KnownServers { file, name, trust_new } => {
+                known_servers::check(file, name, observed, *trust_new)
+            },
+        }
+    }
+}
+
+// A connection to the server, directly or through a proxy, that can be wrapped in TLS.
+trait Stream: Read + Write + Send + fmt::Debug {}
+
+impl<S: Read + Write + Send + fmt::Debug> Stream for S {}
+
+// A TCP connection that times out when the whole request takes longer than its deadline,
+// instead of only when a single read or write does.
+#[derive(Debug)]
+struct DeadlineStream {
+    inner: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    // Get the time left before the deadline, for the timeout of the next read or write.
+    fn time_left(&self) -> io::Result<Duration> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the request timed out"));
+        }
+
+        Ok(self.deadline - now)
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.set_read_timeout(Some(self.time_left()?))?;
+        self.inner.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.set_write_timeout(Some(self.time_left()?))?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Sets up TLS connections to the server and gets the fingerprint of its certificate.
+//
+// The certificate chain and host name are not validated by the TLS library,
+// so a connection must not be used before its fingerprint is checked.
+// The timeout bounds everything from connecting to reading the end of the response.
+pub(crate) struct Connector {
+    server: String,
+    port: u16,
+    proxy: Option<reqwest::Url>,
+    proxy_auth: Option<(String, String)>,
+    tls: native_tls::TlsConnector,
+    connect_timeout: Duration,
+    timeout: Duration,
+}
+
+impl Connector {
+    pub(crate) fn new(
+        server: &str,
+        port: u16,
+        proxy: &ProxySettings,
+        identity: Option<&ClientIdentity>,
+        connect_timeout: Duration,
+        timeout: Duration
+        ) -> Result<Self>
+    {
+        let proxy_url = match proxy.url(server) {
+            Some(t) => {
+                let url = reqwest::Url::parse(t.as_str()).map_err(|e| {
+                    Error::Custom(format!("'{}' is not a valid proxy URL: {}", t, e))
+                })?;
+                Some(url)
+            },
+            None => None,
+        };
+
+        let proxy_auth = match proxy_url {
+            _ if !proxy.user.is_empty() => Some((proxy.user.clone(), proxy.password.clone())),
+            Some(ref url) if !url.username().is_empty() => {
+                Some((url.username().to_string(), url.password().unwrap_or("").to_string()))
+            },
+            _ => None,
+        };
+
+        let mut builder = native_tls::TlsConnector::builder();
+        builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_hostnames(true);
+
+        if let Some(identity) = identity {
+            builder.identity(identity.native_identity()?);
+        }
+
+        let tls = builder.build().map_err(|e| {
+            Error::Custom(format!("Failed to set up TLS: {}", e))
+        })?;
+
+        Ok(Connector {
+            server: server.to_string(),
+            port,
+            proxy: proxy_url,
+            proxy_auth,
+            tls,
+            connect_timeout,
+            timeout,
+        })
+    }
+
+    // Connect to the server with TLS and get the fingerprint of its certificate.
+    fn connect(&self) -> Result<(TlsStream<Box<dyn Stream>>, String)> {
+        let deadline = Instant::now() + self.timeout;
+
+        let stream = match self.proxy {
+            Some(ref proxy) => self.connect_proxy(proxy, deadline)?,
+            None => Box::new(self.connect_tcp(self.server.as_str(), self.port, deadline)?),
+        };
+
+        let tls_error = |e: &dyn fmt::Display| {
+            Error::Custom(format!("Failed to set up TLS with {}:{}: {}",
+                                  request::host(self.server.as_str()), self.port, e))
+        };
+
+        let stream = self.tls.connect(self.server.as_str(), stream).map_err(|e| tls_error(&e))?;
+
+        let cert = match stream.peer_certificate().map_err(|e| tls_error(&e))? {
+            Some(t) => t,
+            None => return Err(tls_error(&"the server did not send a certificate")),
+        };
+
+        let der = cert.to_der().map_err(|e| tls_error(&e))?;
+
+        Ok((stream, tls::format_fingerprint(&der)))
+    }
+
+    // Connect to the server and get the SHA-256 fingerprint of its certificate.
+    pub(crate) fn server_fingerprint(&self) -> Result<String> {
+        let (_, fingerprint) = self.connect()?;
+
+        Ok(fingerprint)
+    }
+
+    // Connect to each address of a host until one accepts the connection.
+    fn connect_tcp(&self, host: &str, port: u16, deadline: Instant) -> Result<DeadlineStream> {
+        let mut last_error = None;
+
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(inner) => return Ok(DeadlineStream { inner, deadline }),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(Error::Io(e)),
+            None => Err(Error::Custom(format!("Failed to resolve the address of {}", host))),
+        }
+    }
+
+    // Connect to the server through the proxy, the same way as reqwest.
+    fn connect_proxy(&self, proxy: &reqwest::Url, deadline: Instant) -> Result<Box<dyn Stream>> {
+        let proxy_host = match proxy.host_str() {
+            Some(t) => t.trim_start_matches('[').trim_end_matches(']'),
+            None => return Err(Error::Custom(format!("The proxy URL {} has no host", proxy))),
+        };
+        let proxy_port = proxy.port_or_known_default().unwrap_or(1080);
+
+        match proxy.scheme() {
+            "http" => {
+                let stream = self.connect_tcp(proxy_host, proxy_port, deadline)?;
+                self.tunnel(Box::new(stream))
+            },
+            "https" => {
+                // The certificate of the proxy itself is validated as usual.
+                let tls_error = |e: &dyn fmt::Display| {
+                    Error::Custom(format!("Failed to set up TLS with the proxy {}: {}",
+                                          proxy_host, e))
+                };
+
+                let stream = self.connect_tcp(proxy_host, proxy_port, deadline)?;
+                let tls = native_tls::TlsConnector::new().map_err(|e| tls_error(&e))?;
+                let stream = tls.connect(proxy_host, stream).map_err(|e| tls_error(&e))?;
+                self.tunnel(Box::new(stream))
+            },
+            "socks5" | "socks5h" => {
+                let stream = self.connect_tcp(proxy_host, proxy_port, deadline)?;
+                self.socks5(Box::new(stream), proxy.scheme() == "socks5h")
+            },
+            scheme => Err(Error::Custom(format!("The proxy scheme {} is not supported", scheme))),
+        }
+    }
+
+    // Ask an HTTP proxy to open a tunnel to the server.
+    fn tunnel(&self, mut stream: Box<dyn Stream>) -> Result<Box<dyn Stream>> {
+        let host = format!("{}:{}", request::host(self.server.as_str()), self.port);
+
+        let mut connect = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", host, host);
+        if let Some((ref user, ref password)) = self.proxy_auth {
+            let credentials = base64::encode(format!("{}:{}", user, password).as_bytes());
+            connect.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+        }
+        connect.push_str("\r\n");
+
+        stream.write_all(connect.as_bytes())?;
+        stream.flush()?;
+
+        // Read one byte at a time so nothing of the TLS handshake is read with the answer.
+        let mut answer = Vec::new();
+        let mut byte = [0; 1];
+        while !answer.ends_with(b"\r\n\r\n") {
+            if stream.read(&mut byte)? == 0 {
+                let msg = format!("The proxy closed the connection to {}", host);
+                return Err(Error::Custom(msg));
+            }
+            answer.push(byte[0]);
+        }
+
+        let answer = String::from_utf8_lossy(&answer);
+        let status = answer.lines().next().unwrap_or("");
+        if status.split_whitespace().nth(1) != Some("200") {
+            let msg = format!("The proxy failed to connect to {}: {}", host, status);
+            return Err(Error::Custom(msg));
+        }
+
+        Ok(stream)
+    }
+
+    // Ask a SOCKS5 proxy to connect to the server.
+    //
+    // With remote_dns, the server name is resolved by the proxy.
+    fn socks5(&self, mut stream: Box<dyn Stream>, remote_dns: bool) -> Result<Box<dyn Stream>> {
+        let socks_error = |what: &str| {
+            Error::Custom(format!("The SOCKS5 proxy failed to connect to {}:{}: {}",
+                                  request::host(self.server.as_str()), self.port, what))
+        };
+
+        // No authentication, or a user and password.
+        let greeting: &[u8] = match self.proxy_auth {
+            Some(_) => &[5, 2, 0, 2],
+            None => &[5, 1, 0],
+        };
+        stream.write_all(greeting)?;
+
+        let mut answer = [0; 2];
+        stream.read_exact(&mut answer)?;
+        match (answer, &self.proxy_auth) {
+            ([5, 0], _) => (),
+            ([5, 2], Some((user, password))) => {
+                if user.len() > 255 || password.len() > 255 {
+                    return Err(socks_error("the user or password is longer than 255 bytes"));
+                }
+
+                let mut auth = vec![1, user.len() as u8];
+                auth.extend_from_slice(user.as_bytes());
+                auth.push(password.len() as u8);
+                auth.extend_from_slice(password.as_bytes());
+                stream.write_all(&auth)?;
+
+                stream.read_exact(&mut answer)?;
+                if answer[1] != 0 {
+                    return Err(socks_error("the user or password was refused"));
+                }
+            },
+            _ => return Err(socks_error("no accepted authentication method")),
+        }
+
+        let host = request::host(self.server.as_str());
+        let ip = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) if remote_dns => None,
+            Err(_) => match (self.server.as_str(), self.port).to_socket_addrs()?.next() {
+                Some(t) => Some(t.ip()),
+                None => {
+                    let msg = format!("Failed to resolve the address of {}", self.server);
+                    return Err(Error::Custom(msg));
+                }
+            },
+        };
+
+        let mut connect = vec![5, 1, 0];
+        match ip {
+            Some(IpAddr::V4(ip)) => {
+                connect.push(1);
+                connect.extend_from_slice(&ip.octets());
+            },
+            Some(IpAddr::V6(ip)) => {
+                connect.push(4);
+                connect.extend_from_slice(&ip.octets());
+            },
+            None if self.server.len() > 255 => {
+                return Err(socks_error("the server name is longer than 255 bytes"));
+            },
+            None => {
+                connect.push(3);
+                connect.push(self.server.len() as u8);
+                connect.extend_from_slice(self.server.as_bytes());
+            },
+        }
+        connect.extend_from_slice(&self.port.to_be_bytes());
+        stream.write_all(&connect)?;
+        stream.flush()?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            return Err(socks_error(&format!("reply {}", reply[1])));
+        }
+
+        // Skip the address the proxy bound to, and its port.
+        let length = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => {
+                let mut length = [0; 1];
+                stream.read_exact(&mut length)?;
+                usize::from(length[0])
+            },
+            t => return Err(socks_error(&format!("invalid address type {}", t))),
+        };
+        let mut bound = vec![0; length + 2];
+        stream.read_exact(&mut bound)?;
+
+        Ok(stream)
+    }
+}
+
+impl fmt::Debug for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connector")
+            .field("server", &self.server)
+            .field("port", &self.port)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+// The Transport used when the server certificate is pinned.
+//
+// reqwest can only accept any certificate or validate the chain, and it reconnects
+// on its own, so the pin can't be checked on its connections. This sends each command
+// over a new connection instead, and checks the certificate of the connection
+// before anything is sent on it.
+#[derive(Debug)]
+pub(crate) struct PinnedTransport {
+    base_url: String,
+    connector: Connector,
+    pin: Pin,
+}
+
+impl PinnedTransport {
+    pub(crate) fn new(base_url: &str, connector: Connector, pin: Pin) -> Self {
+        PinnedTransport {
+            base_url: base_url.to_string(),
+            connector,
+            pin,
+        }
+    }
+}
+
+impl Transport for PinnedTransport {
+    fn send(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportResponse>
+    {
+        let mut stream = self.send_streaming(command, headers, payload)?;
+
+        let mut body = Vec::new();
+        stream.body.read_to_end(&mut body)?;
+
+        Ok(TransportResponse {
+            status: stream.status,
+            headers: stream.headers,
+            body,
+        })
+    }
+
+    fn send_streaming(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportStream>
+    {
+        let url = request::url(self.base_url.as_str(), command);
+        let url = reqwest::Url::parse(url.as_str()).map_err(|e| {
+            Error::Custom(format!("'{}' is not a valid URL: {}", url, e))
+        })?;
+
+        let (mut stream, fingerprint) = self.connector.connect()?;
+        self.pin.check(fingerprint.as_str())?;
+
+        let body = serde_json::to_vec(payload)?;
+
+        let mut request = format!("POST {} HTTP/1.1\r\nHost: {}:{}\r\n", url.path(),
+                                  request::host(self.connector.server.as_str()),
+                                  self.connector.port).into_bytes();
+        for (name, value) in headers {
+            request.extend_from_slice(name.as_str().as_bytes());
+            request.extend_from_slice(b": ");
+            request.extend_from_slice(value.as_bytes());
+            request.extend_from_slice(b"\r\n");
+        }
+        request.extend_from_slice(format!("Content-Length: {}\r\nConnection: close\r\n\r\n",
+                                          body.len()).as_bytes());
+        request.extend_from_slice(&body);
+
+        stream.write_all(&request)?;
+        stream.flush()?;
+
+        read_response(BufReader::new(stream))
+    }
+}
+
+// Read the status and headers of a response and return the body as a reader.
+fn read_response<R: BufRead + Send + 'static>(mut reader: R) -> Result<TransportStream> {
+    let invalid = |what: &str| Error::Custom(format!("Failed to read the HTTP response: {}", what));
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let status = match line.split_whitespace().nth(1).map(|s| s.parse::<u16>()) {
+        Some(Ok(t)) if line.starts_with("HTTP/") => t,
+        _ => return Err(invalid(&format!("invalid status line '{}'", line.trim_end()))),
+    };
+
+    // The header lines are read as bytes, since a value does not have to be UTF-8.
+    let mut headers = HeaderMap::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(invalid("the connection closed in the headers"));
+        }
+
+        let header = trim(&line);
+        if header.is_empty() {
+            break;
+        }
+
+        let invalid_header = || {
+            invalid(&format!("invalid header '{}'", String::from_utf8_lossy(header)))
+        };
+
+        let colon = match header.iter().position(|b| *b == b':') {
+            Some(t) => t,
+            None => return Err(invalid_header()),
+        };
+
+        // Like reqwest, a value that is not visible ASCII is kept as it is.
+        let name = HeaderName::from_bytes(trim(&header[..colon])).map_err(|_| invalid_header())?;
+        let value = HeaderValue::from_bytes(trim(&header[colon + 1..]))
+            .map_err(|_| invalid_header())?;
+        headers.append(name, value);
+    }
+
+    let chunked = headers.get_all("transfer-encoding").iter()
+        .any(|v| v.to_str().map(|s| s.to_lowercase().contains("chunked")).unwrap_or(false));
+
+    let length = match headers.get("content-length") {
+        Some(t) => match t.to_str().ok().and_then(|s| s.parse::<u64>().ok()) {
+            Some(t) => Some(t),
+            None => {
+                let msg = format!("invalid Content-Length {:?}", t);
+                return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidData, msg)));
+            }
+        },
+        None => None,
+    };
+
+    let body: Box<dyn Read + Send> = if chunked {
+        Box::new(Chunked { inner: reader, remaining: 0, done: false })
+    }
+    else if let Some(length) = length {
+        Box::new(reader.take(length))
+    }
+    else {
+        Box::new(reader)
+    };
+
+    Ok(TransportStream { status, headers, body })
+}
+
+// Remove the whitespace, including the line ending, around a header.
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
+    let end = bytes.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(start, |i| i + 1);
+
+    &bytes[start..end]
+}
+
+// Read a body with chunked transfer encoding.
+struct Chunked<R> {
+    inner: R,
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            let mut line = String::new();
+            if self.inner.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "missing chunk"));
+            }
+
+            let size = line.split(';').next().unwrap_or("").trim();
+            self.remaining = u64::from_str_radix(size, 16).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size")
+            })?;
+
+            // The trailers after the last chunk are not needed, the connection is closed.
+            if self.remaining == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated chunk"));
+        }
+
+        self.remaining -= n as u64;
+        if self.remaining == 0 {
+            let mut end = [0; 2];
+            self.inner.read_exact(&mut end)?;
+            if end != *b"\r\n" {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid chunk end"));
+            }
+        }
+
+        Ok(n)
+    }
+}
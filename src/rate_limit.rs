@@ -0,0 +1,82 @@
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+// The longest wait for a token, so a very low rate does not overflow the Duration.
+const MAX_WAIT_SECS: f64 = 24.0 * 60.0 * 60.0;
+
+// A token bucket that limits the rate of the requests sent to the server.
+//
+// The bucket holds up to burst tokens and refills at rate tokens per second.
+// Each request takes a token, waiting for the bucket to refill when it is empty.
+// It is shared by the Clients created with login_to_domain, so they stay under the rate together.
+pub(crate) struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate: f64, burst: u32) -> Result<Self> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            let msg = format!("The rate limit must be a positive finite number, got {}", rate);
+            return Err(Error::Custom(msg));
+        }
+
+        if burst == 0 {
+            return Err(Error::Custom(String::from("The rate limit burst must be at least 1")));
+        }
+
+        let burst = f64::from(burst);
+
+        Ok(RateLimiter {
+            rate,
+            burst,
+            bucket: Mutex::new(Bucket { tokens: burst, last: Instant::now() }),
+        })
+    }
+
+    // Take a token and get how long to wait before sending the request.
+    //
+    // The token is taken even if the bucket is empty, so the requests waiting
+    // at the same time are sent one after the other. The wait is at most a day.
+    pub(crate) fn reserve(&self) -> Duration {
+        let mut bucket = self.lock();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.last = now;
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst) - 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        }
+        else {
+            Duration::from_secs_f64((-bucket.tokens / self.rate).min(MAX_WAIT_SECS))
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Bucket> {
+        // A poisoned lock only means a thread panicked while taking a token.
+        match self.bucket.lock() {
+            Ok(t) => t,
+            Err(e) => e.into_inner(),
+        }
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("rate", &self.rate)
+            .field("burst", &self.burst)
+            .finish()
+    }
+}
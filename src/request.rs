@@ -0,0 +1,384 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
+use serde_json::json;
+
+use crate::credentials::Credentials;
+use crate::response::Response;
+use crate::version::ApiVersion;
+use crate::error::{Error, Result};
+
+// Request building and Response handling shared by the blocking and async Clients.
+
+// The parts of a base URL such as https://[fd00::10]:443/web_api.
+pub(crate) struct BaseUrl {
+    pub(crate) scheme: String,
+    pub(crate) server: String,
+    pub(crate) port: u16,
+    pub(crate) api_path: String,
+}
+
+// Parse a base URL. The API path is web_api if the URL has no path.
+pub(crate) fn parse_base_url(base_url: &str) -> Result<BaseUrl> {
+    let invalid = |reason: &str| {
+        Error::Custom(format!("'{}' is not a valid base URL: {}", base_url, reason))
+    };
+
+    let url = reqwest::Url::parse(base_url).map_err(|e| invalid(&e.to_string()))?;
+
+    let scheme = url.scheme().to_string();
+    if scheme != "https" && scheme != "http" {
+        return Err(invalid("the scheme must be https or http"));
+    }
+
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid("a query or fragment is not allowed"));
+    }
+
+    let server = match url.host_str() {
+        Some(t) => t.trim_start_matches('[').trim_end_matches(']').to_string(),
+        None => return Err(invalid("there is no server")),
+    };
+
+    let port = match url.port_or_known_default() {
+        Some(t) => t,
+        None => return Err(invalid("there is no port")),
+    };
+
+    let api_path = match api_path(url.path()) {
+        ref t if t.is_empty() => String::from("web_api"),
+        t => t,
+    };
+
+    Ok(BaseUrl { scheme, server, port, api_path })
+}
+
+// Remove the slashes around an API path such as /web_api/.
+pub(crate) fn api_path(path: &str) -> String {
+    path.trim_matches('/').to_string()
+}
+
+// Put an IPv6 address in brackets for a URL.
+pub(crate) fn host(server: &str) -> String {
+    if server.contains(':') && !server.starts_with('[') {
+        format!("[{}]", server)
+    }
+    else {
+        server.to_string()
+    }
+}
+
+// Build the base URL of the API, such as https://192.168.1.10:443/web_api,
+// or https://192.168.1.10:443/web_api/v1.5 with a pinned API version.
+pub(crate) fn base_url(
+    scheme: &str,
+    server: &str,
+    port: u16,
+    api_path: &str,
+    api_version: Option<&str>
+    ) -> String
+{
+    let mut base = format!("{}://{}:{}", scheme, host(server), port);
+
+    if !api_path.is_empty() {
+        base = format!("{}/{}", base, api_path);
+    }
+
+    if let Some(version) = api_version {
+        base = format!("{}/v{}", base, version);
+    }
+
+    base
+}
+
+// Build the URL of a command.
+pub(crate) fn url(base_url: &str, command: &str) -> String {
+    format!("{}/{}", base_url, command)
+}
+
+// Generate the headers for a Request
+pub(crate) fn headers(sid: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(USER_AGENT, HeaderValue::from_static("cp_api"));
+
+    if !sid.is_empty() {
+        let n = HeaderName::from_static("x-chkp-sid");
+        let v = HeaderValue::from_str(sid)?;
+
+        headers.insert(n, v);
+    }
+
+    Ok(headers)
+}
+
+// Build the login payload from the Credentials and the session settings.
+pub(crate) fn login_payload(
+    credentials: &Credentials,
+    domain: &str,
+    session_timeout: u64,
+    read_only: bool,
+    continue_last_session: bool
+    ) -> serde_json::Value
+{
+    let mut payload = credentials.payload();
+    payload["domain"] = json!(domain);
+    payload["session-timeout"] = json!(session_timeout);
+    payload["read-only"] = json!(read_only);
+    payload["continue-last-session"] = json!(continue_last_session);
+
+    payload
+}
+
+// The session values from a successful login.
+pub(crate) struct LoginSession {
+    pub(crate) sid: String,
+    pub(crate) uid: String,
+    pub(crate) api_server_version: String,
+}
+
+// Get the session values from a successful login.
+//
+// The uid is empty for a read only session.
+pub(crate) fn login_session(login: &Response, read_only: bool) -> Result<LoginSession> {
+    let sid = match login.data["sid"].as_str() {
+        Some(t) => t,
+        None => return Err(Error::InvalidResponse("sid", json!(login)))
+    }.to_string();
+
+    let api_server_version = match login.data["api-server-version"].as_str() {
+        Some(t) => t,
+        None => return Err(Error::InvalidResponse("api-server-version", json!(login)))
+    }.to_string();
+
+    let uid = if read_only {
+        String::new()
+    }
+    else {
+        match login.data["uid"].as_str() {
+            Some(t) => t,
+            None => return Err(Error::InvalidResponse("uid", json!(login)))
+        }.to_string()
+    };
+
+    Ok(LoginSession { sid, uid, api_server_version })
+}
+
+// Check that a call or query was successful for call_and_check and query_and_check.
+pub(crate) fn check(command: &str, res: Result<Response>) -> Result<Response> {
+    let res = match res {
+        Ok(t) => t,
+        Err(e) => {
+            let msg = format!("Failed to run command, '{}': {}", command, e);
+            return Err(Error::Custom(msg));
+        }
+    };
+
+    if res.is_not_success() {
+        let msg = format!("'{}' was not successful. status: {}, code: {}, message: {}",
+                            command, res.status(), res.data["code"], res.data["message"]);
+        return Err(Error::Custom(msg));
+    }
+
+    Ok(res)
+}
+
+// The paging state of a query.
+pub(crate) struct Query {
+    limit: u64,
+    offset: u64,
+    to: u64,
+    total: u64,
+    payload: serde_json::Value,
+    objects: Vec<serde_json::Value>,
+}
+
+impl Query {
+    pub(crate) fn new(payload: serde_json::Value) -> Result<Query> {
+        let limit = payload.get("limit")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(50);
+
+        let offset = payload.get("offset")
+            .and_then(|t| t.as_u64())
+            .unwrap_or(0);
+
+        let payload = build_query_payload(payload, offset)?;
+
+        Ok(Query {
+            limit,
+            offset,
+            to: 0,
+            total: 1,
+            payload,
+            objects: Vec::new(),
+        })
+    }
+
+    // Check if there are more pages to get.
+    pub(crate) fn has_next(&self) -> bool {
+        self.to != self.total
+    }
+
+    // The payload for the next page.
+    pub(crate) fn payload(&self) -> serde_json::Value {
+        self.payload.clone()
+    }
+
+    // Add the objects from a page and move the offset to the next page.
+    pub(crate) fn add_page(&mut self, res: &mut Response) -> Result<()> {
+        self.next_page(res)?;
+
+        let objects = match res.data["objects"].as_array_mut() {
+            Some(t) => t,
+            None => return Err(Error::InvalidResponse("objects", json!(res)))
+        };
+
+        self.objects.append(objects);
+
+        Ok(())
+    }
+
+    // Move the offset to the next page without keeping the objects of the page.
+    pub(crate) fn next_page(&mut self, res: &Response) -> Result<()> {
+        if res.is_not_success() {
+            let msg = format!("Received an unsuccessful Response from the API \
+                               while running a query. Error code: {}, message: {}",
+                               res.data["code"], res.data["message"]);
+            return Err(Error::Custom(msg));
+        }
+
+        self.to = match res.data["to"].as_u64() {
+            Some(t) => t,
+            None => return Err(Error::InvalidResponse("to", json!(res)))
+        };
+
+        self.total = match res.data["total"].as_u64() {
+            Some(t) => t,
+            None => return Err(Error::InvalidResponse("total", json!(res)))
+        };
+
+        self.offset += self.limit;
+
+        if let Some(obj) = self.payload.get_mut("offset") {
+            *obj = json!(self.offset);
+        }
+        else {
+            let msg = String::from("Failed to get the offset to update from payload");
+            return Err(Error::Custom(msg));
+        }
+
+        Ok(())
+    }
+
+    // Build the Response with every object from the last page's Response.
+    pub(crate) fn finish(self, mut res: Response) -> Response {
+        res.objects = self.objects;
+        res.data = json!({});
+
+        res
+    }
+}
+
+// Build the query payload
+fn build_query_payload(mut payload: serde_json::Value, offset: u64) -> Result<serde_json::Value> {
+    let payload_map = match payload.as_object_mut() {
+        Some(t) => t,
+        None => {
+            let msg = String::from("Failed to parse payload for query");
+            return Err(Error::Custom(msg));
+        }
+    };
+
+    let offset_json = match serde_json::to_value(offset) {
+        Ok(t) => t,
+        Err(e) => {
+            let msg = format!("Failed to convert offset for query: {}", e);
+            return Err(Error::Custom(msg));
+        }
+    };
+
+    payload_map.insert("offset".to_string(), offset_json);
+
+    Ok(json!(payload_map))
+}
+
+// Get the task-ids to wait for from a Response, if any.
+//
+// The first value is true when the Response has a single task-id,
+// false when it has a list of tasks.
+pub(crate) fn task_ids(res: &Response) -> Option<(bool, Vec<String>)> {
+    if let Some(id) = res.data.get("task-id").and_then(|t| t.as_str()) {
+        return Some((true, vec![id.to_string()]));
+    }
+
+    let tasks = res.data.get("tasks")?.as_array()?;
+    let ids = tasks.iter()
+        .filter_map(|t| t.get("task-id").and_then(|id| id.as_str()))
+        .map(|id| id.to_string())
+        .collect();
+
+    Some((false, ids))
+}
+
+// Pick the highest API version from show-api-versions between min and max, inclusive.
+//
+// The version is returned along with how the server wrote it, to use in the URLs,
+// since the server serves /v1/ but not /v1.0/.
+pub(crate) fn negotiate_api_version(
+    res: &Response,
+    min: ApiVersion,
+    max: ApiVersion
+    ) -> Result<(ApiVersion, String)>
+{
+    if res.is_not_success() {
+        let msg = format!("'show-api-versions' was not successful. status: {}, code: {}, \
+                           message: {}", res.status(), res.data["code"], res.data["message"]);
+        return Err(Error::Custom(msg));
+    }
+
+    let supported = match res.data["supported-versions"].as_array() {
+        Some(t) => t,
+        None => return Err(Error::InvalidResponse("supported-versions", json!(res)))
+    };
+
+    let versions: Vec<&str> = supported.iter().filter_map(|v| v.as_str()).collect();
+
+    let best = versions.iter()
+        .filter_map(|v| v.parse::<ApiVersion>().ok().map(|t| (t, v.trim().trim_start_matches('v'))))
+        .filter(|(v, _)| *v >= min && *v <= max)
+        .max_by_key(|(v, _)| *v);
+
+    match best {
+        Some((version, name)) => Ok((version, name.to_string())),
+        None => {
+            let msg = format!("None of the API versions supported by the server are \
+                               between {} and {}. Supported versions: {}",
+                              min, max, versions.join(", "));
+            Err(Error::Custom(msg))
+        }
+    }
+}
+
+// The payload for show-task.
+pub(crate) fn show_task_payload<T: serde::Serialize>(task_id: T) -> serde_json::Value {
+    json!({"task-id": task_id, "details-level": "full"})
+}
+
+// Print the progress of a task from show-task and check if it is still running.
+pub(crate) fn task_in_progress(command: &str, res: &Response) -> Result<bool> {
+    let percent = match res.data["tasks"][0].get("progress-percentage") {
+        Some(t) => t,
+        None => return Err(Error::InvalidResponse("progress-percentage", json!(res)))
+    };
+
+    let status = match res.data["tasks"][0].get("status") {
+        Some(t) => t,
+        None => return Err(Error::InvalidResponse("status", json!(res)))
+    };
+
+    println!("{} {} - {}%", command, status, percent);
+
+    Ok(status == "in progress")
+}
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 
+use reqwest::header::HeaderMap;
+
 use serde_json::json;
 use serde::Serialize;
 
+use crate::stream;
 use crate::error::{Error, Result};
 
 /// A Response from the API.
@@ -35,33 +38,55 @@ impl Response {
         }
     }
 
-    // Set the values for a Response.
-    pub(crate) fn set(reqwest_response: &mut reqwest::Response) -> Result<Response> {
+    // Create a Response from data that was not received over HTTP.
+    pub(crate) fn from_data(status: u16, url: &str, data: serde_json::Value) -> Response {
         let mut res = Response::new();
 
-        res.status = reqwest_response.status().as_u16();
-        if res.is_success() {
-            res.success = true;
-        }
-        else {
-            res.success = false;
-        }
+        res.status = status;
+        res.success = res.is_success();
+        res.url = url.to_string();
+        res.data = data;
 
-        res.url = reqwest_response.url().to_string();
+        res
+    }
 
-        res.data = match reqwest_response.json() {
+    // Create a Response from the parts of an HTTP response.
+    //
+    // This is shared by the blocking and async Clients. The body can be gzip compressed.
+    pub(crate) fn from_parts(
+        status: u16,
+        url: &str,
+        headers: &HeaderMap,
+        body: &[u8]
+        ) -> Result<Response>
+    {
+        let body = stream::decode_all(headers, body)?;
+
+        let data = match serde_json::from_slice(&body) {
             Ok(t) => t,
             Err(e) => {
                 let msg = format!("Response body received is not valid JSON. \
-                                   Error code: {}, message: {}", res.status(), e);
+                                   Error code: {}, message: {}", status, e);
                 return Err(Error::Custom(msg));
             }
         };
 
-        let reqwest_headers = reqwest_response.headers();
+        Response::from_value(status, url, headers, data)
+    }
+
+    // Create a Response from the status, headers and parsed body of an HTTP response.
+    pub(crate) fn from_value(
+        status: u16,
+        url: &str,
+        headers: &HeaderMap,
+        data: serde_json::Value
+        ) -> Result<Response>
+    {
+        let mut res = Response::from_data(status, url, data);
+
         let mut map = HashMap::new();
 
-        for (k, v) in reqwest_headers.iter() {
+        for (k, v) in headers.iter() {
             let k = k.as_str().to_string();
             let v = v.to_str()?;
             let v = v.to_string();
@@ -113,12 +138,12 @@ impl Response {
 
     /// Check if the status is between 200-299 and is successful.
     pub fn is_success(&self) -> bool {
-        self.status >= 200 && self.status < 300 && self.success == true
+        self.status >= 200 && self.status < 300 && self.success
     }
 
     /// Check if the status is not successful.
     pub fn is_not_success(&self) -> bool {
-        self.status < 200 || self.status >= 300 || self.success == false
+        self.status < 200 || self.status >= 300 || !self.success
     }
 
     /// Check if the status is between 300-399.
@@ -0,0 +1,100 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crate::response::Response;
+use crate::error::Error;
+
+/// How `Client::call()` retries transient failures, set with `Client::retry_policy()`.
+///
+/// A call is retried after a network error, a timeout,
+/// a 5xx status or a 429 "too many requests" status.
+/// The delay doubles after each attempt, up to max_delay.
+///
+/// Only the show-* commands are retried by default, since retrying a command that changes
+/// the configuration after a network error could run it twice.
+///
+/// ```
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     commands: vec![String::from("set-host")],
+///     ..Default::default()
+/// };
+/// client.retry_policy(policy);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// The maximum delay between two attempts.
+    pub max_delay: Duration,
+
+    /// Wait a random time between half and all of the delay,
+    /// so that many clients do not retry at the same time.
+    pub jitter: bool,
+
+    /// Other commands to retry along with the show-* commands.
+    pub commands: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Check if a command can be retried.
+    pub(crate) fn retries(&self, command: &str) -> bool {
+        command.starts_with("show-") || self.commands.iter().any(|c| c == command)
+    }
+
+    /// The delay to wait after a failed attempt, the first attempt being 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.initial_delay.checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        if !self.jitter {
+            return delay;
+        }
+
+        // A random number without adding a dependency, RandomState is randomly seeded.
+        let random = (RandomState::new().build_hasher().finish() % 1000) as f64 / 1000.0;
+
+        delay.mul_f64(0.5 + random / 2.0)
+    }
+}
+
+// Check if a Response is a transient failure.
+pub(crate) fn is_retryable_response(res: &Response) -> bool {
+    let status = res.status();
+
+    status == 429 || (500..600).contains(&status)
+}
+
+// Check if an error is a transient failure, such as a reset connection or a timeout.
+pub(crate) fn is_retryable_error(e: &Error) -> bool {
+    match e {
+        Error::Reqwest(e) => e.is_timeout() || e.is_http() || e.is_server_error(),
+        Error::Io(e) => matches!(e.kind(), ErrorKind::ConnectionReset
+                                            | ErrorKind::ConnectionAborted
+                                            | ErrorKind::BrokenPipe
+                                            | ErrorKind::TimedOut
+                                            | ErrorKind::Interrupted
+                                            | ErrorKind::UnexpectedEof),
+        _ => false,
+    }
+}
@@ -0,0 +1,102 @@
+use std::ops::{Deref, DerefMut};
+
+use serde_json::json;
+
+use crate::client::Client;
+use crate::response::Response;
+use crate::error::Result;
+
+/// A scoped session created with `Client::session()`.
+///
+/// Changes made through the Session are published only when `Session::commit()` is called.
+/// If the Session is dropped before that, such as when an error is returned with `?`,
+/// the changes are discarded. A read only Client has no changes, so it does not discard them.
+///
+/// The Session derefs to the Client, so any Client method can be used with it.
+///
+/// ```
+/// let mut session = client.session("Add web servers")?;
+/// session.call_and_check("add-host", json!({"name": "web1", "ip-address": "10.1.1.21"}))?;
+/// session.call_and_check("add-host", json!({"name": "web2", "ip-address": "10.1.1.22"}))?;
+/// session.commit()?;
+/// ```
+#[derive(Debug)]
+pub struct Session<'a> {
+    client: &'a mut Client,
+    finished: bool,
+}
+
+impl<'a> Session<'a> {
+    // Set the session description and create the Session.
+    pub(crate) fn new(client: &'a mut Client, description: &str) -> Result<Session<'a>> {
+        client.call_and_check("set-session", json!({"description": description}))?;
+
+        Ok(Session {
+            client,
+            finished: false,
+        })
+    }
+
+    /// Publish the changes made in the Session.
+    ///
+    /// If the publish is not successful an error is returned and the changes are discarded.
+    /// ```
+    /// let publish = session.commit()?;
+    /// ```
+    pub fn commit(mut self) -> Result<Response> {
+        let publish = self.client.call_and_check("publish", json!({}))?;
+        self.finished = true;
+
+        Ok(publish)
+    }
+
+    /// Discard the changes made in the Session.
+    ///
+    /// This is the same as dropping the Session, but the Response can be checked.
+    /// ```
+    /// let discard = session.discard()?;
+    /// assert!(discard.is_success());
+    /// ```
+    pub fn discard(mut self) -> Result<Response> {
+        self.finished = true;
+
+        self.client.call("discard", json!({}))
+    }
+}
+
+impl<'a> Deref for Session<'a> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl<'a> DerefMut for Session<'a> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client
+    }
+}
+
+impl<'a> Drop for Session<'a> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        // A read only Client refuses the discard, and has no changes to discard.
+        if self.client.is_refused("discard") {
+            return;
+        }
+
+        // Same as the Client, there is no way to return an error while being dropped.
+        match self.client.call("discard", json!({})) {
+            Ok(ref t) if t.is_not_success() => {
+                eprintln!("Failed to discard the changes while dropping the Session: {}",
+                          t.data["message"]);
+            },
+            Ok(_) => (),
+            Err(e) => eprintln!("Error discarding the changes while dropping the Session: {}", e),
+        }
+    }
+}
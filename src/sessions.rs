@@ -0,0 +1,249 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::client::Client;
+use crate::login_info::Timestamp;
+use crate::response::Response;
+use crate::error::{Error, Result};
+
+/// A session returned from show-sessions.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub uid: String,
+    pub name: String,
+    pub user_name: String,
+    pub description: String,
+
+    /// The application that created the session, such as "WEB_API" or "SmartConsole".
+    pub application: String,
+
+    /// The state of the session, such as "open".
+    pub state: String,
+    pub changes: u64,
+    pub locks: u64,
+    pub expired_session: bool,
+    pub in_work: bool,
+    pub ip_address: String,
+    pub creation_time: Option<Timestamp>,
+    pub last_login_time: Option<Timestamp>,
+}
+
+impl SessionInfo {
+    // Parse a session object from show-sessions.
+    fn from_value(value: &serde_json::Value) -> Result<SessionInfo> {
+        let uid = match value["uid"].as_str() {
+            Some(t) => t.to_string(),
+            None => return Err(Error::InvalidResponse("uid", value.clone()))
+        };
+
+        let text = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+
+        Ok(SessionInfo {
+            uid,
+            name: text("name"),
+            user_name: text("user-name"),
+            description: text("description"),
+            application: text("application"),
+            state: text("state"),
+            changes: value["changes"].as_u64().unwrap_or(0),
+            locks: value["locks"].as_u64().unwrap_or(0),
+            expired_session: value["expired-session"].as_bool().unwrap_or(false),
+            in_work: value["in-work"].as_bool().unwrap_or(false),
+            ip_address: text("ip-address"),
+            creation_time: Timestamp::from_value(&value["creation-time"]),
+            last_login_time: Timestamp::from_value(&value["last-login-time"]),
+        })
+    }
+
+    // Get the time since the last login, if it is known.
+    fn age(&self) -> Option<Duration> {
+        let last_login = self.last_login_time.as_ref()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+
+        now.checked_sub(Duration::from_millis(last_login.posix))
+    }
+}
+
+/// The rules for which sessions `Client::cleanup_sessions()` discards.
+///
+/// The defaults only discard WEB_API sessions without changes or locks,
+/// and do a dry run that discards nothing.
+///
+/// ```
+/// let policy = CleanupPolicy {
+///     min_age: Some(Duration::from_secs(24 * 60 * 60)),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct CleanupPolicy {
+    /// Only discard sessions from these applications. An empty list matches every application.
+    pub applications: Vec<String>,
+
+    /// Only discard sessions whose last login was at least this long ago.
+    pub min_age: Option<Duration>,
+
+    /// Discard sessions that have changes.
+    pub with_changes: bool,
+
+    /// Discard sessions that have locks.
+    pub with_locks: bool,
+
+    /// Only report which sessions would be discarded.
+    pub dry_run: bool,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        CleanupPolicy {
+            applications: vec![String::from("WEB_API")],
+            min_age: None,
+            with_changes: false,
+            with_locks: false,
+            dry_run: true,
+        }
+    }
+}
+
+impl CleanupPolicy {
+    // Get the reason a session should not be discarded.
+    fn skip_reason(&self, session: &SessionInfo) -> Option<String> {
+        if !self.applications.is_empty() && !self.applications.contains(&session.application) {
+            return Some(format!("application is {}", session.application));
+        }
+
+        if !self.with_changes && session.changes > 0 {
+            return Some(format!("has {} changes", session.changes));
+        }
+
+        if !self.with_locks && session.locks > 0 {
+            return Some(format!("has {} locks", session.locks));
+        }
+
+        if let Some(min_age) = self.min_age {
+            match session.age() {
+                Some(age) if age >= min_age => (),
+                Some(age) => return Some(format!("last login was {} seconds ago", age.as_secs())),
+                None => return Some(String::from("last login time is unknown")),
+            }
+        }
+
+        None
+    }
+}
+
+/// The result of `Client::cleanup_sessions()`.
+#[derive(Clone, Debug, Serialize)]
+pub struct CleanupReport {
+    /// True if nothing was discarded.
+    pub dry_run: bool,
+
+    /// The sessions that were discarded, or would be discarded in a dry run.
+    pub discarded: Vec<SessionInfo>,
+
+    /// The sessions that were skipped with the reason.
+    pub skipped: Vec<(SessionInfo, String)>,
+
+    /// The sessions that failed to be discarded with the error.
+    pub failed: Vec<(SessionInfo, String)>,
+}
+
+impl Client {
+    /// Get all the sessions.
+    ///
+    /// ```
+    /// for session in client.show_sessions()? {
+    ///     println!("{} - {} changes, {} locks", session.user_name, session.changes, session.locks);
+    /// }
+    /// ```
+    pub fn show_sessions(&mut self) -> Result<Vec<SessionInfo>> {
+        let res = self.query_and_check("show-sessions", json!({"details-level": "full"}))?;
+
+        res.objects.iter()
+            .map(SessionInfo::from_value)
+            .collect()
+    }
+
+    /// Discard the changes of a session.
+    /// ```
+    /// let discard = client.discard_session("6aec3a51-4a54-4a6e-8c3b-8d3c5b5a3e3f")?;
+    /// assert!(discard.is_success());
+    /// ```
+    pub fn discard_session(&mut self, uid: &str) -> Result<Response> {
+        self.call("discard", json!({"uid": uid}))
+    }
+
+    /// Take over a session from another administrator.
+    ///
+    /// Set disconnect_active to true to take over a session that is still in use.
+    /// ```
+    /// let take_over = client.take_over_session("6aec3a51-4a54-4a6e-8c3b-8d3c5b5a3e3f", false)?;
+    /// assert!(take_over.is_success());
+    /// ```
+    pub fn take_over_session(&mut self, uid: &str, disconnect_active: bool) -> Result<Response> {
+        let payload = json!({
+            "uid": uid,
+            "disconnect-active-session": disconnect_active,
+        });
+
+        self.call("take-over-session", payload)
+    }
+
+    /// Discard the sessions that match a CleanupPolicy.
+    ///
+    /// The session of this Client is always skipped.
+    /// With the default policy this is a dry run, so check the report
+    /// and then run it again with dry_run set to false.
+    ///
+    /// ```
+    /// let mut policy = CleanupPolicy::default();
+    /// let report = client.cleanup_sessions(&policy)?;
+    /// for session in &report.discarded {
+    ///     println!("Will discard {} from {}", session.uid, session.user_name);
+    /// }
+    ///
+    /// policy.dry_run = false;
+    /// client.cleanup_sessions(&policy)?;
+    /// ```
+    pub fn cleanup_sessions(&mut self, policy: &CleanupPolicy) -> Result<CleanupReport> {
+        let mut report = CleanupReport {
+            dry_run: policy.dry_run,
+            discarded: Vec::new(),
+            skipped: Vec::new(),
+            failed: Vec::new(),
+        };
+
+        for session in self.show_sessions()? {
+            let reason = if session.uid == self.uid() {
+                Some(String::from("this is the session of the Client"))
+            }
+            else {
+                policy.skip_reason(&session)
+            };
+
+            if let Some(reason) = reason {
+                report.skipped.push((session, reason));
+                continue;
+            }
+
+            if policy.dry_run {
+                report.discarded.push(session);
+                continue;
+            }
+
+            match self.discard_session(session.uid.as_str()) {
+                Ok(ref t) if t.is_success() => report.discarded.push(session),
+                Ok(t) => {
+                    let msg = format!("status: {}, code: {}, message: {}",
+                                      t.status(), t.data["code"], t.data["message"]);
+                    report.failed.push((session, msg));
+                },
+                Err(e) => report.failed.push((session, e.to_string())),
+            }
+        }
+
+        Ok(report)
+    }
+}
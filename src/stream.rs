@@ -0,0 +1,169 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{BufReader, Read};
+
+use flate2::read::GzDecoder;
+use reqwest::header::{HeaderMap, CONTENT_ENCODING};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::error::{Error, Result};
+
+// Check if a body is gzip compressed.
+//
+// reqwest decompresses the body itself and removes the header,
+// so this is only true for a body from another Transport.
+pub(crate) fn is_gzip(headers: &HeaderMap) -> bool {
+    headers.get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false)
+}
+
+// Decompress a whole body if it is gzip compressed.
+pub(crate) fn decode_all<'a>(headers: &HeaderMap, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+    if !is_gzip(headers) {
+        return Ok(Cow::Borrowed(body));
+    }
+
+    let mut buf = Vec::new();
+    GzDecoder::new(body).read_to_end(&mut buf)?;
+
+    Ok(Cow::Owned(buf))
+}
+
+// The error of parse_page, kept apart so the error of the callback is returned unchanged.
+#[derive(Debug)]
+pub(crate) enum PageError {
+    // Reading or parsing the body failed.
+    Read(Error),
+
+    // The callback returned an error.
+    Callback(Error),
+}
+
+// Parse a query page while it is read, passing each of its objects to on_object.
+//
+// Only one object is kept in memory at a time. The rest of the page is returned,
+// such as from, to and total, or the code and message of an error.
+pub(crate) fn parse_page<R, F>(
+    status: u16,
+    headers: &HeaderMap,
+    body: R,
+    on_object: &mut F
+    ) -> std::result::Result<serde_json::Value, PageError>
+    where R: Read,
+          F: FnMut(serde_json::Value) -> Result<()>
+{
+    let body: Box<dyn Read> = if is_gzip(headers) {
+        Box::new(GzDecoder::new(body))
+    }
+    else {
+        Box::new(body)
+    };
+
+    let mut callback_error = None;
+    let mut de = serde_json::Deserializer::from_reader(BufReader::new(body));
+
+    let seed = PageSeed { on_object, callback_error: &mut callback_error };
+    let parsed = seed.deserialize(&mut de).and_then(|data| de.end().map(|_| data));
+
+    match (parsed, callback_error) {
+        (_, Some(e)) => Err(PageError::Callback(e)),
+        (Ok(data), None) => Ok(serde_json::Value::Object(data)),
+        (Err(e), None) => {
+            let msg = format!("Response body received is not valid JSON. \
+                               Error code: {}, message: {}", status, e);
+            Err(PageError::Read(Error::Custom(msg)))
+        }
+    }
+}
+
+// Parses the fields of a page, streaming the objects field to the callback.
+struct PageSeed<'a, F> {
+    on_object: &'a mut F,
+    callback_error: &'a mut Option<Error>,
+}
+
+impl<'de, 'a, F> DeserializeSeed<'de> for PageSeed<'a, F>
+    where F: FnMut(serde_json::Value) -> Result<()>
+{
+    type Value = serde_json::Map<String, serde_json::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, F> Visitor<'de> for PageSeed<'a, F>
+    where F: FnMut(serde_json::Value) -> Result<()>
+{
+    type Value = serde_json::Map<String, serde_json::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON object")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+        where A: MapAccess<'de>
+    {
+        let mut data = serde_json::Map::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "objects" {
+                map.next_value_seed(ObjectsSeed {
+                    on_object: &mut *self.on_object,
+                    callback_error: &mut *self.callback_error,
+                })?;
+            }
+            else {
+                data.insert(key, map.next_value()?);
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+// Parses the objects array one object at a time.
+struct ObjectsSeed<'a, F> {
+    on_object: &'a mut F,
+    callback_error: &'a mut Option<Error>,
+}
+
+impl<'de, 'a, F> DeserializeSeed<'de> for ObjectsSeed<'a, F>
+    where F: FnMut(serde_json::Value) -> Result<()>
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F> Visitor<'de> for ObjectsSeed<'a, F>
+    where F: FnMut(serde_json::Value) -> Result<()>
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of objects")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<(), A::Error>
+        where A: SeqAccess<'de>
+    {
+        while let Some(object) = seq.next_element::<serde_json::Value>()? {
+            if let Err(e) = (self.on_object)(object) {
+                // Stop parsing, the error from the callback is returned instead.
+                *self.callback_error = Some(e);
+                return Err(de::Error::custom("stopped by the callback"));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fmt, fs};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+// A client certificate and key presented to the server.
+#[derive(Clone)]
+pub(crate) enum ClientIdentity {
+    Pkcs12 {
+        file: String,
+        password: String,
+    },
+    Pem {
+        cert: String,
+        key: String,
+    },
+}
+
+impl ClientIdentity {
+    // Get the identity for reqwest.
+    pub(crate) fn reqwest_identity(&self) -> Result<reqwest::Identity> {
+        let (der, password) = self.pkcs12()?;
+
+        Ok(reqwest::Identity::from_pkcs12_der(&der, password.as_str())?)
+    }
+
+    // Get the identity for native-tls.
+    pub(crate) fn native_identity(&self) -> Result<native_tls::Identity> {
+        let (der, password) = self.pkcs12()?;
+
+        native_tls::Identity::from_pkcs12(&der, password.as_str()).map_err(|e| {
+            Error::Custom(format!("Failed to read the client identity {}: {}", self.file(), e))
+        })
+    }
+
+    fn file(&self) -> &str {
+        match self {
+            ClientIdentity::Pkcs12 { file, .. } => file.as_str(),
+            ClientIdentity::Pem { cert, .. } => cert.as_str(),
+        }
+    }
+
+    // Get the PKCS#12 archive and its password.
+    fn pkcs12(&self) -> Result<(Vec<u8>, String)> {
+        match self {
+            ClientIdentity::Pkcs12 { file, password } => Ok((fs::read(file)?, password.clone())),
+            ClientIdentity::Pem { cert, key } => {
+                let der = pem_to_pkcs12(&fs::read(cert)?, &fs::read(key)?).map_err(|e| {
+                    Error::Custom(format!("Failed to read the client identity {} and {}: {}",
+                                          cert, key, e))
+                })?;
+
+                Ok((der, String::new()))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientIdentity::Pkcs12 { file, .. } => {
+                f.debug_struct("Pkcs12")
+                    .field("file", file)
+                    .field("password", &"*****")
+                    .finish()
+            },
+            ClientIdentity::Pem { cert, key } => {
+                f.debug_struct("Pem")
+                    .field("cert", cert)
+                    .field("key", key)
+                    .finish()
+            },
+        }
+    }
+}
+
+// Convert a PEM certificate and key to a PKCS#12 archive without a password.
+//
+// reqwest only reads a client identity from PKCS#12 with the native TLS backend.
+// Any certificates after the first one in the certificate file are added as the chain.
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+fn pem_to_pkcs12(cert: &[u8], key: &[u8]) -> std::result::Result<Vec<u8>, String> {
+    use openssl::{pkcs12::Pkcs12, pkey::PKey, stack::Stack, x509::X509};
+
+    let run = || -> std::result::Result<Vec<u8>, openssl::error::ErrorStack> {
+        let mut certs = X509::stack_from_pem(cert)?.into_iter();
+        let key = PKey::private_key_from_pem(key)?;
+
+        let mut chain = Stack::new()?;
+        let first = match certs.next() {
+            Some(t) => t,
+            None => return Err(openssl::error::ErrorStack::get()),
+        };
+        for c in certs {
+            chain.push(c)?;
+        }
+
+        Pkcs12::builder()
+            .name("cp_api")
+            .pkey(&key)
+            .cert(&first)
+            .ca(chain)
+            .build2("")?
+            .to_der()
+    };
+
+    run().map_err(|e| e.to_string())
+}
+
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+fn pem_to_pkcs12(_cert: &[u8], _key: &[u8]) -> std::result::Result<Vec<u8>, String> {
+    Err(String::from("a PEM client identity is not supported on this platform, \
+                      convert it with 'openssl pkcs12 -export' and use PKCS#12"))
+}
+
+// Read the certificates in a file.
+//
+// The file is either a binary DER encoded certificate,
+// or one or more PEM encoded certificates such as a CA bundle.
+pub(crate) fn certificates(file: &str) -> Result<Vec<reqwest::Certificate>> {
+    let buf = fs::read(file)?;
+
+    let text = match std::str::from_utf8(&buf) {
+        Ok(t) if t.contains("-----BEGIN CERTIFICATE-----") => t,
+        _ => return Ok(vec![reqwest::Certificate::from_der(&buf)?]),
+    };
+
+    let mut certs = Vec::new();
+    let end = "-----END CERTIFICATE-----";
+
+    for block in text.split_inclusive(end).filter(|b| b.contains(end)) {
+        let start = match block.find("-----BEGIN CERTIFICATE-----") {
+            Some(t) => t,
+            None => {
+                let msg = format!("Found the end of a certificate without the beginning in {}",
+                                  file);
+                return Err(Error::Custom(msg));
+            }
+        };
+
+        certs.push(reqwest::Certificate::from_pem(&block.as_bytes()[start..])?);
+    }
+
+    Ok(certs)
+}
+
+// Format the SHA-256 fingerprint of a DER encoded certificate,
+// the same as `openssl x509 -fingerprint -sha256`.
+pub(crate) fn format_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der).iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+// Normalize a SHA-256 fingerprint to upper case hex separated with colons.
+//
+// The fingerprint can be given with or without colons or spaces, in upper or lower case.
+pub(crate) fn normalize_fingerprint(fingerprint: &str) -> Result<String> {
+    let hex: String = fingerprint.chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        let msg = format!("'{}' is not a SHA-256 fingerprint", fingerprint);
+        return Err(Error::Custom(msg));
+    }
+
+    let pairs: Vec<&str> = (0..64).step_by(2).map(|i| &hex[i..i + 2]).collect();
+
+    Ok(pairs.join(":"))
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::io::{Cursor, Read};
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+use crate::request;
+use crate::error::Result;
+
+/// The status, headers and body returned by a Transport.
+#[derive(Clone, Debug)]
+pub struct TransportResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TransportResponse {
+    /// Create a TransportResponse with a JSON body.
+    /// ```
+    /// let res = TransportResponse::json(200, &json!({"sid": "abc", "uid": "123"}));
+    /// ```
+    pub fn json(status: u16, data: &serde_json::Value) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        TransportResponse {
+            status,
+            headers,
+            body: data.to_string().into_bytes(),
+        }
+    }
+}
+
+/// The status and headers returned by a Transport, with the body still to be read.
+///
+/// This is returned by `Transport::send_streaming()` for `Client::query_each()`.
+pub struct TransportStream {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl fmt::Debug for TransportStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TransportStream")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// Sends the API commands for a Client.
+///
+/// The default is `ReqwestTransport` which sends the commands over HTTPS.
+/// Any other Transport is set with `Client::transport()`,
+/// such as one that answers from memory in tests or replays recorded calls.
+///
+/// Any closure taking the command, headers and payload is a Transport too.
+///
+/// ```
+/// client.transport(|command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+///     match command {
+///         "login" => Ok(TransportResponse::json(200, &json!({
+///             "sid": "abc", "uid": "123", "api-server-version": "1.5", "session-timeout": 600
+///         }))),
+///         _ => Ok(TransportResponse::json(404, &json!({"code": "not_found"}))),
+///     }
+/// });
+/// ```
+pub trait Transport: Send + Sync {
+    /// Send a command with the headers and payload.
+    fn send(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportResponse>;
+
+    /// Send a command and return the body as a reader,
+    /// so a large Response is parsed while it is received.
+    ///
+    /// The default reads the whole body with `Transport::send()`.
+    fn send_streaming(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportStream>
+    {
+        let res = self.send(command, headers, payload)?;
+
+        Ok(TransportStream {
+            status: res.status,
+            headers: res.headers,
+            body: Box::new(Cursor::new(res.body)),
+        })
+    }
+}
+
+impl<F> Transport for F
+    where F: Fn(&str, &HeaderMap, &serde_json::Value) -> Result<TransportResponse> + Send + Sync
+{
+    fn send(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportResponse>
+    {
+        self(command, headers, payload)
+    }
+}
+
+/// The default Transport that sends the commands with reqwest.
+///
+/// The Client builds it from its certificate, proxy and timeout settings,
+/// but it can also be created to wrap it in another Transport, such as one that records the calls.
+///
+/// ```
+/// let reqwest_client = reqwest::Client::new();
+/// let transport = ReqwestTransport::new("192.168.1.10", 443, reqwest_client);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReqwestTransport {
+    base_url: String,
+    reqwest_client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Create a ReqwestTransport to send commands to the server with a reqwest client.
+    ///
+    /// The commands are sent to https://server:port/web_api.
+    pub fn new(server: &str, port: u16, reqwest_client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            base_url: request::base_url("https", server, port, "web_api", None),
+            reqwest_client,
+        }
+    }
+
+    /// Create a ReqwestTransport to send commands to a base URL with a reqwest client.
+    /// ```
+    /// let transport = ReqwestTransport::with_base_url("https://[fd00::10]:443/gaia_api",
+    ///                                                 reqwest_client);
+    /// ```
+    pub fn with_base_url(base_url: &str, reqwest_client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            reqwest_client,
+        }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportResponse>
+    {
+        let mut stream = self.send_streaming(command, headers, payload)?;
+
+        let mut body = Vec::new();
+        stream.body.read_to_end(&mut body)?;
+
+        Ok(TransportResponse {
+            status: stream.status,
+            headers: stream.headers,
+            body,
+        })
+    }
+
+    fn send_streaming(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportStream>
+    {
+        let url = request::url(self.base_url.as_str(), command);
+
+        let reqwest_response = self.reqwest_client.post(url.as_str())
+            .headers(headers.clone())
+            .json(payload)
+            .send()?;
+
+        // reqwest asks for a gzip compressed body and decompresses it while it is read.
+        Ok(TransportStream {
+            status: reqwest_response.status().as_u16(),
+            headers: reqwest_response.headers().clone(),
+            body: Box::new(reqwest_response),
+        })
+    }
+}
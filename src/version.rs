@@ -0,0 +1,75 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// A Management API version that can be compared.
+///
+/// ```
+/// let version: ApiVersion = client.api_server_version().parse()?;
+/// if version < ApiVersion::new(1, 5) {
+///     eprintln!("API version {} is too old", version);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ApiVersion {
+    /// Create a new ApiVersion with a major and minor number.
+    pub fn new(major: u32, minor: u32) -> Self {
+        ApiVersion {
+            major,
+            minor,
+            patch: 0,
+        }
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = Error;
+
+    /// Parse a version such as "1.5", "1.6.1" or "v1.5".
+    fn from_str(s: &str) -> Result<ApiVersion> {
+        let trimmed = s.trim().trim_start_matches('v');
+        let parts: Vec<&str> = trimmed.split('.').collect();
+
+        if parts.len() > 3 {
+            let msg = format!("Failed to parse API version \"{}\"", s);
+            return Err(Error::Custom(msg));
+        }
+
+        let mut numbers = [0; 3];
+        for (i, part) in parts.iter().enumerate() {
+            numbers[i] = match part.parse() {
+                Ok(t) => t,
+                Err(_) => {
+                    let msg = format!("Failed to parse API version \"{}\"", s);
+                    return Err(Error::Custom(msg));
+                }
+            };
+        }
+
+        Ok(ApiVersion {
+            major: numbers[0],
+            minor: numbers[1],
+            patch: numbers[2],
+        })
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        }
+        else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
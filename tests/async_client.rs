@@ -0,0 +1,107 @@
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::time::Duration;
+
+use cp_api::{AsyncClient, Error, Result};
+use futures_util::future::TryFutureExt;
+use serde_json::json;
+
+mod common;
+use common::start_slow_server;
+
+// Run the future on a tokio 0.1 runtime, which the async reqwest client needs.
+fn block_on<T, F>(f: F) -> Result<T>
+    where T: Send + 'static,
+          F: Future<Output = Result<T>> + Send + 'static
+{
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(Box::pin(f).compat())
+}
+
+#[test]
+fn login_query_logout() {
+    block_on(async {
+        let mut client = AsyncClient::new("172.25.199.80", 443);
+        client.accept_invalid_certs(true);
+        client.log_file("async_client.txt");
+
+        let login = client.login("cp_api", "vpn123").await?;
+        assert!(login.is_success());
+        assert!(!client.sid().is_empty());
+        assert!(client.login_info().is_some());
+
+        let hosts = client.query_and_check("show-hosts", json!({"details-level": "standard"}))
+            .await?;
+        assert!(!hosts.objects.is_empty());
+
+        client.call_and_check("logout", json!({})).await?;
+        client.save_log()?;
+
+        Ok(())
+    }).unwrap();
+}
+
+#[test]
+fn read_only_refused() {
+    let res = block_on(async {
+        // Nothing listens on this port, so a call that is sent fails to connect.
+        let mut client = AsyncClient::new("127.0.0.1", 1);
+        client.read_only(true);
+
+        match client.call("add-host", json!({})).await {
+            Err(Error::ReadOnly(c)) => assert_eq!("add-host", c),
+            r => panic!("add-host was not refused: {:?}", r),
+        }
+
+        client.call("show-hosts", json!({})).await
+    });
+
+    match res {
+        Err(Error::ReadOnly(_)) => panic!("show-hosts was refused"),
+        Err(_) => (),
+        Ok(_) => panic!("show-hosts should fail to connect"),
+    }
+}
+
+#[test]
+fn override_classification() {
+    let res = block_on(async {
+        let mut client = AsyncClient::new("127.0.0.1", 1);
+        client.read_only(true);
+        client.mutating_command("show-hosts", true);
+        client.mutating_command("add-host", false);
+
+        match client.call("show-hosts", json!({})).await {
+            Err(Error::ReadOnly(c)) => assert_eq!("show-hosts", c),
+            r => panic!("show-hosts was not refused: {:?}", r),
+        }
+
+        client.call("add-host", json!({})).await
+    });
+
+    match res {
+        Err(Error::ReadOnly(_)) => panic!("add-host was refused"),
+        Err(_) => (),
+        Ok(_) => panic!("add-host should fail to connect"),
+    }
+}
+
+#[test]
+fn command_timeout() {
+    let port = start_slow_server(Duration::from_secs(2));
+
+    block_on(async move {
+        let mut client = AsyncClient::new("127.0.0.1", port);
+        client.accept_invalid_certs(true);
+        client.request_timeout(1);
+        client.command_timeout("publish", 5);
+
+        let res = client.call("publish", json!({})).await?;
+        assert!(res.is_success());
+
+        assert!(client.call("show-hosts", json!({})).await.is_err());
+
+        Ok(())
+    }).unwrap();
+}
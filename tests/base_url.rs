@@ -0,0 +1,96 @@
+use std::io::Write;
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use cp_api::Client;
+use serde_json::json;
+
+mod common;
+use common::{read_request, EMPTY_OBJECT, SERVER_FINGERPRINT};
+
+// Start a plain HTTP stand-in server that answers one request with an empty JSON object
+// and sends the request line it received.
+fn start_stand_in(addr: &str) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind(addr).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = read_request(&mut stream);
+
+        let _ = stream.write_all(EMPTY_OBJECT);
+
+        let request = String::from_utf8_lossy(&request).to_string();
+        tx.send(request.lines().next().unwrap_or("").to_string()).unwrap();
+    });
+
+    (port, rx)
+}
+
+#[test]
+fn ipv6_server() {
+    assert_eq!("https://[fd00::10]:443/web_api", Client::new("fd00::10", 443).base_url());
+    assert_eq!("https://[fd00::10]:443/web_api", Client::new("[fd00::10]", 443).base_url());
+    assert_eq!("https://192.168.1.10:443/web_api", Client::new("192.168.1.10", 443).base_url());
+}
+
+#[test]
+fn from_url() {
+    let client = Client::from_url("https://[fd00::10]/gaia_api/").unwrap();
+    assert_eq!("https://[fd00::10]:443/gaia_api", client.base_url());
+
+    let client = Client::from_url("https://mgmt.example.com:4434").unwrap();
+    assert_eq!("https://mgmt.example.com:4434/web_api", client.base_url());
+
+    let client = Client::from_url("http://127.0.0.1:8080/checkpoint/web_api").unwrap();
+    assert_eq!("http://127.0.0.1:8080/checkpoint/web_api", client.base_url());
+
+    assert!(Client::from_url("ftp://192.168.1.10/web_api").is_err());
+    assert!(Client::from_url("https://192.168.1.10/web_api?x=1").is_err());
+    assert!(Client::from_url("192.168.1.10").is_err());
+}
+
+#[test]
+fn api_path() {
+    let mut client = Client::new("192.168.1.10", 443);
+    client.api_path("/checkpoint/web_api/");
+
+    assert_eq!("https://192.168.1.10:443/checkpoint/web_api", client.base_url());
+}
+
+#[test]
+fn plain_http_stand_in() {
+    let (port, rx) = start_stand_in("127.0.0.1:0");
+    let mut client = Client::from_url(&format!("http://127.0.0.1:{}/stand_in/web_api", port))
+        .unwrap();
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+    assert_eq!("POST /stand_in/web_api/show-hosts HTTP/1.1", rx.recv().unwrap());
+}
+
+#[test]
+fn ipv6_stand_in() {
+    // Skip when IPv6 is not available.
+    if TcpListener::bind("[::1]:0").is_err() {
+        return;
+    }
+
+    let (port, rx) = start_stand_in("[::1]:0");
+    let mut client = Client::from_url(&format!("http://[::1]:{}", port)).unwrap();
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+    assert_eq!(format!("http://[::1]:{}/web_api/show-hosts", port), res.url());
+    assert_eq!("POST /web_api/show-hosts HTTP/1.1", rx.recv().unwrap());
+}
+
+#[test]
+fn fingerprint_over_http() {
+    let mut client = Client::from_url("http://127.0.0.1:1/web_api").unwrap();
+    client.certificate_fingerprint(SERVER_FINGERPRINT);
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
@@ -0,0 +1,192 @@
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+use cp_api::{Client, Transport};
+
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+
+pub const SERVER_FINGERPRINT: &str = "82:00:F1:5B:2A:75:CF:D1:07:87:DE:39:BE:A9:36:09:\
+                                      27:31:D0:12:97:2C:D6:D0:F6:B4:D2:B6:4E:33:3E:01";
+
+pub const EMPTY_OBJECT: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                              Content-Length: 2\r\nConnection: close\r\n\r\n{}";
+
+// A Client that sends the commands to a Transport instead of a server.
+pub fn mock_client<T: Transport + 'static>(transport: T) -> Client {
+    let mut client = Client::new("192.168.1.10", 443);
+    client.transport(transport);
+    client
+}
+
+// A Client for the TLS servers below, with their certificate pinned.
+pub fn pinned_client(port: u16) -> Client {
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate_fingerprint(SERVER_FINGERPRINT);
+    client
+}
+
+// Start a TLS server with the self-signed certificate in tests/data
+// that answers every request with an empty JSON object.
+pub fn start_server() -> u16 {
+    start_server_answering(EMPTY_OBJECT)
+}
+
+// Start the same TLS server, but answer every request with the given HTTP response.
+pub fn start_server_answering(response: &'static [u8]) -> u16 {
+    let p12 = std::fs::read("tests/data/server.p12").unwrap();
+    let identity = native_tls::Identity::from_pkcs12(&p12, "cp_api").unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Ok(stream) = acceptor.accept(stream.unwrap()) {
+                answer_with(stream, response);
+            }
+        }
+    });
+
+    port
+}
+
+// Start the same TLS server, but wait before answering each request.
+pub fn start_slow_server(delay: Duration) -> u16 {
+    let p12 = std::fs::read("tests/data/server.p12").unwrap();
+    let identity = native_tls::Identity::from_pkcs12(&p12, "cp_api").unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let acceptor = acceptor.clone();
+            thread::spawn(move || {
+                if let Ok(stream) = acceptor.accept(stream.unwrap()) {
+                    thread::sleep(delay);
+                    answer(stream);
+                }
+            });
+        }
+    });
+
+    port
+}
+
+// Start the same TLS server, but send the body of the response one byte at a time,
+// waiting between each byte.
+pub fn start_trickling_server(delay: Duration) -> u16 {
+    let p12 = std::fs::read("tests/data/server.p12").unwrap();
+    let identity = native_tls::Identity::from_pkcs12(&p12, "cp_api").unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let acceptor = acceptor.clone();
+            thread::spawn(move || {
+                if let Ok(mut stream) = acceptor.accept(stream.unwrap()) {
+                    read_request(&mut stream);
+
+                    let headers = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                    Content-Length: 100\r\nConnection: close\r\n\r\n";
+                    let _ = stream.write_all(headers);
+                    for byte in [b' '; 98].iter().chain(b"{}") {
+                        thread::sleep(delay);
+                        if stream.write_all(&[*byte]).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    port
+}
+
+// Start a TLS server that presents the certificate of start_server() on the first connection,
+// and the client certificate in tests/data on the connections after it,
+// as a server that is swapped after the fingerprint was read would.
+pub fn start_switching_server() -> u16 {
+    let acceptor = |file: &str| {
+        let p12 = std::fs::read(file).unwrap();
+        let identity = native_tls::Identity::from_pkcs12(&p12, "cp_api").unwrap();
+        native_tls::TlsAcceptor::new(identity).unwrap()
+    };
+    let first = acceptor("tests/data/server.p12");
+    let other = acceptor("tests/data/client.p12");
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for (i, stream) in listener.incoming().enumerate() {
+            let acceptor = if i == 0 { &first } else { &other };
+            if let Ok(stream) = acceptor.accept(stream.unwrap()) {
+                answer(stream);
+            }
+        }
+    });
+
+    port
+}
+
+// Start the same TLS server, but require the client certificate in tests/data.
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+pub fn start_mtls_server() -> u16 {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    builder.set_private_key_file("tests/data/server.key", SslFiletype::PEM).unwrap();
+    builder.set_certificate_chain_file("tests/data/server.pem").unwrap();
+    builder.set_ca_file("tests/data/client.pem").unwrap();
+    builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    let acceptor = builder.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Ok(stream) = acceptor.accept(stream.unwrap()) {
+                answer(stream);
+            }
+        }
+    });
+
+    port
+}
+
+// Read the request until the end of the JSON payload and answer with an empty JSON object.
+fn answer<S: Read + Write>(stream: S) {
+    answer_with(stream, EMPTY_OBJECT);
+}
+
+fn answer_with<S: Read + Write>(mut stream: S, response: &[u8]) {
+    read_request(&mut stream);
+
+    let _ = stream.write_all(response);
+}
+
+// Read a request until the end of its JSON payload.
+pub fn read_request<S: Read>(stream: &mut S) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    while !request.ends_with(b"}") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    request
+}
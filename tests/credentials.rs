@@ -0,0 +1,101 @@
+use cp_api::{CredentialProvider, Credentials, EnvCredentials, NetrcCredentials};
+use std::env;
+use std::fs;
+
+#[test]
+fn static_credentials() {
+    let provider = Credentials::api_key("eXaMpLeKeY123==");
+
+    match provider.credentials("192.168.1.10", "").unwrap() {
+        Credentials::ApiKey(key) => assert_eq!("eXaMpLeKeY123==", key),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+}
+
+#[test]
+fn debug_hides_secrets() {
+    let password = format!("{:?}", Credentials::password("admin", "vpn123"));
+    assert!(password.contains("admin"));
+    assert!(!password.contains("vpn123"));
+
+    let api_key = format!("{:?}", Credentials::api_key("eXaMpLeKeY123=="));
+    assert!(!api_key.contains("eXaMpLeKeY123=="));
+}
+
+#[test]
+fn env_credentials() {
+    env::set_var("CP_API_TEST_ENV_USER", "admin");
+    env::set_var("CP_API_TEST_ENV_PASSWORD", "vpn123");
+
+    let provider = EnvCredentials::with_prefix("CP_API_TEST_ENV");
+
+    match provider.credentials("192.168.1.10", "").unwrap() {
+        Credentials::Password { user, password } => {
+            assert_eq!("admin", user);
+            assert_eq!("vpn123", password);
+        },
+        c => panic!("wrong credentials: {:?}", c),
+    }
+
+    env::set_var("CP_API_TEST_ENV_KEY", "eXaMpLeKeY123==");
+
+    match provider.credentials("192.168.1.10", "").unwrap() {
+        Credentials::ApiKey(key) => assert_eq!("eXaMpLeKeY123==", key),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+}
+
+#[test]
+fn env_credentials_missing() {
+    let provider = EnvCredentials::with_prefix("CP_API_TEST_MISSING");
+    assert!(provider.credentials("192.168.1.10", "").is_err());
+}
+
+#[test]
+fn netrc_credentials() {
+    let file = env::temp_dir().join("cp_api_netrc_test");
+    fs::write(&file, "\
+# lab servers
+machine 192.168.1.10 login admin password vpn123
+machine 192.168.1.20 login mds password abc123
+machine 192.168.1.20 domain \"Domain A\" api-key eXaMpLeKeY123==
+default login readonly password ro123
+").unwrap();
+
+    let provider = NetrcCredentials::new(file.to_str().unwrap());
+
+    match provider.credentials("192.168.1.10", "").unwrap() {
+        Credentials::Password { user, password } => {
+            assert_eq!("admin", user);
+            assert_eq!("vpn123", password);
+        },
+        c => panic!("wrong credentials: {:?}", c),
+    }
+
+    match provider.credentials("192.168.1.20", "Domain A").unwrap() {
+        Credentials::ApiKey(key) => assert_eq!("eXaMpLeKeY123==", key),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+
+    match provider.credentials("192.168.1.20", "Domain B").unwrap() {
+        Credentials::Password { user, .. } => assert_eq!("mds", user),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+
+    match provider.credentials("192.168.1.30", "").unwrap() {
+        Credentials::Password { user, .. } => assert_eq!("readonly", user),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn closure_credentials() {
+    let provider = |server: &str, _domain: &str| Ok(Credentials::password("admin", server));
+
+    match provider.credentials("192.168.1.10", "").unwrap() {
+        Credentials::Password { password, .. } => assert_eq!("192.168.1.10", password),
+        c => panic!("wrong credentials: {:?}", c),
+    }
+}
@@ -1,4 +1,5 @@
 use cp_api::Client;
+use serde_json::json;
 
 #[test]
 fn get_headers() {
@@ -17,7 +18,7 @@ fn display_objects() {
     client.accept_invalid_certs(true);
     client.login("cp_api", "vpn123").unwrap();
 
-    let hosts = client.query("show-hosts", "standard").unwrap();
+    let hosts = client.query("show-hosts", json!({"details-level": "standard"})).unwrap();
     println!("{:#?}", hosts.objects);
 
     client.logout().unwrap();
@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+
+use cp_api::{Client, DomainManager, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+// A Multi-Domain Server Client whose Transport records the commands sent with their sid.
+fn mds(sent: Arc<Mutex<Vec<(String, String)>>>) -> Client {
+    let mut mds = Client::new("192.168.1.10", 443);
+    mds.transport(move |command: &str, headers: &HeaderMap, payload: &serde_json::Value| {
+        let sid = headers.get("x-chkp-sid")
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        sent.lock().unwrap().push((command.to_string(), sid));
+
+        let data = match command {
+            "login" => json!({
+                "sid": "mds", "uid": "1", "api-server-version": "1.5", "session-timeout": 600
+            }),
+            "login-to-domain" => json!({
+                "sid": payload["domain"], "uid": "2", "api-server-version": "1.5",
+                "session-timeout": 600
+            }),
+            _ => json!({}),
+        };
+        Ok(TransportResponse::json(200, &data))
+    });
+    mds.login("user", "pass").unwrap();
+    mds
+}
+
+#[test]
+fn drop_logs_out_domains_first() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+
+    let mut domains = DomainManager::new(mds(Arc::clone(&sent)));
+    domains.client("Domain A").unwrap();
+    domains.client("Domain B").unwrap();
+    drop(domains);
+
+    let logouts: Vec<String> = sent.lock().unwrap().iter()
+        .filter(|(command, _)| command == "logout")
+        .map(|(_, sid)| sid.clone())
+        .collect();
+
+    assert_eq!(3, logouts.len());
+    assert_eq!("mds", logouts[2]);
+    assert!(logouts[..2].contains(&String::from("Domain A")));
+    assert!(logouts[..2].contains(&String::from("Domain B")));
+}
+
+#[test]
+fn shutdown_logs_out_once() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+
+    let mut domains = DomainManager::new(mds(Arc::clone(&sent)));
+    domains.client("Domain A").unwrap();
+    domains.shutdown().unwrap();
+
+    let logouts: Vec<String> = sent.lock().unwrap().iter()
+        .filter(|(command, _)| command == "logout")
+        .map(|(_, sid)| sid.clone())
+        .collect();
+
+    assert_eq!(vec!["Domain A", "mds"], logouts);
+}
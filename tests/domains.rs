@@ -0,0 +1,26 @@
+use cp_api::{Client, DomainManager};
+use serde_json::json;
+
+#[test]
+fn multiple_domains() {
+    let mut mds = Client::new("10.1.1.110", 443);
+    mds.accept_invalid_certs(true);
+    mds.login("admin", "vpn123").unwrap();
+
+    let mut domains = DomainManager::new(mds);
+
+    let res = domains.call("CheckPoint", "show-host", json!({"name": "host1"})).unwrap();
+    println!("CheckPoint host1: {}", res.data["ipv4-address"]);
+
+    let hosts = domains.query_and_check("Microsoft", "show-hosts", json!({"details-level": "standard"})).unwrap();
+    println!("Microsoft hosts: {}", hosts.objects.len());
+
+    let mut names = domains.domains();
+    names.sort();
+    assert_eq!(vec!["CheckPoint", "Microsoft"], names);
+
+    let first_sid = domains.client("CheckPoint").unwrap().sid().to_string();
+    assert_eq!(first_sid, domains.client("CheckPoint").unwrap().sid());
+
+    domains.shutdown().unwrap();
+}
@@ -2,6 +2,7 @@ use cp_api::Client;
 use std::error::Error;
 
 #[test]
+#[allow(deprecated)]
 fn desc_source() {
     let mut client = Client::new("192.168.1.12", 443);
     client.accept_invalid_certs(true);
@@ -0,0 +1,45 @@
+// The test server is built with OpenSSL.
+#![cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+
+use serde_json::json;
+
+mod common;
+use common::{pinned_client, start_mtls_server};
+
+#[test]
+fn pkcs12_identity() {
+    let port = start_mtls_server();
+    let mut client = pinned_client(port);
+    client.identity_pkcs12("tests/data/client.p12", "cp_api");
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+    assert!(!format!("{:?}", client).contains("\"cp_api\""));
+}
+
+#[test]
+fn pem_identity() {
+    let port = start_mtls_server();
+    let mut client = pinned_client(port);
+    client.identity_pem("tests/data/client.pem", "tests/data/client.key");
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+}
+
+#[test]
+fn missing_identity() {
+    let port = start_mtls_server();
+    let mut client = pinned_client(port);
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
+
+#[test]
+fn wrong_password() {
+    let port = start_mtls_server();
+    let mut client = pinned_client(port);
+    client.identity_pkcs12("tests/data/client.p12", "wrong");
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
@@ -0,0 +1,20 @@
+use cp_api::Client;
+
+#[test]
+fn keepalive_while_idle() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.session_timeout(20);
+    client.login("cp_api", "vpn123").unwrap();
+
+    let keepalive = client.start_keepalive(0.5).unwrap();
+    assert!(keepalive.is_running());
+
+    std::thread::sleep(std::time::Duration::from_secs(45));
+
+    assert!(keepalive.count() >= 2);
+    assert!(keepalive.last_error().is_none());
+
+    client.logout().unwrap();
+    assert!(!keepalive.is_running());
+}
@@ -0,0 +1,96 @@
+use std::env;
+use std::fs;
+
+use cp_api::{Client, Error};
+use serde_json::json;
+
+mod common;
+use common::{start_server, start_switching_server, SERVER_FINGERPRINT};
+
+fn known_servers_file(name: &str) -> String {
+    let file = env::temp_dir().join(format!("cp_api_{}_{}", name, std::process::id()));
+    let _ = fs::remove_file(&file);
+    file.to_str().unwrap().to_string()
+}
+
+fn client(port: u16, file: &str) -> Client {
+    let mut client = Client::new("127.0.0.1", port);
+    client.known_servers(file);
+    client
+}
+
+#[test]
+fn trust_on_first_use() {
+    let port = start_server();
+    let file = known_servers_file("first_use");
+
+    let mut first = client(port, &file);
+    first.trust_on_first_use(true);
+    let res = first.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+
+    let contents = fs::read_to_string(&file).unwrap();
+    assert_eq!(format!("127.0.0.1:{} {}\n", port, SERVER_FINGERPRINT), contents);
+
+    // The server is known now, so a new Client trusts it without adding it again.
+    let res = client(port, &file).call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+    assert_eq!(contents, fs::read_to_string(&file).unwrap());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn changed_certificate() {
+    let port = start_server();
+    let file = known_servers_file("changed");
+    let known = "AB:".repeat(32)[..95].to_string();
+    fs::write(&file, format!("# servers\n127.0.0.1:{} {}\n", port, known)).unwrap();
+
+    match client(port, &file).call("show-hosts", json!({})) {
+        Err(Error::KnownServerChanged(server, old, new)) => {
+            assert_eq!(format!("127.0.0.1:{}", port), server);
+            assert_eq!(known, old);
+            assert_eq!(SERVER_FINGERPRINT, new);
+        },
+        r => panic!("the changed certificate was not detected: {:?}", r),
+    }
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn unknown_server() {
+    let port = start_server();
+    let file = known_servers_file("unknown");
+
+    match client(port, &file).call("show-hosts", json!({})) {
+        Err(Error::UnknownServer(server, fingerprint)) => {
+            assert_eq!(format!("127.0.0.1:{}", port), server);
+            assert_eq!(SERVER_FINGERPRINT, fingerprint);
+        },
+        r => panic!("the unknown server was trusted: {:?}", r),
+    }
+
+    assert!(fs::metadata(&file).is_err());
+}
+
+#[test]
+fn certificate_changed_after_first_use() {
+    let port = start_switching_server();
+    let file = known_servers_file("changed_after_first_use");
+
+    let mut client = client(port, &file);
+    client.trust_on_first_use(true);
+    assert!(client.call("show-hosts", json!({})).unwrap().is_success());
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::KnownServerChanged(_, known, new)) => {
+            assert_eq!(SERVER_FINGERPRINT, known);
+            assert_ne!(SERVER_FINGERPRINT, new);
+        },
+        r => panic!("the changed certificate was not detected: {:?}", r),
+    }
+
+    fs::remove_file(&file).unwrap();
+}
@@ -1,4 +1,4 @@
-use cp_api::Client;
+use cp_api::{Client, Error};
 use serde_json::json;
 
 #[test]
@@ -11,10 +11,32 @@ fn login_logout() {
     assert!(!client.uid().is_empty());
     assert!(!client.api_server_version().is_empty());
 
+    let info = client.login_info().unwrap();
+    assert_eq!(600, info.session_timeout);
+    assert!(!info.read_only);
+    assert_eq!(client.api_server_version(), info.api_version.to_string());
+    println!("{:#?}", info);
+
     client.logout().unwrap();
     assert!(client.sid().is_empty());
     assert!(client.uid().is_empty());
     assert!(client.api_server_version().is_empty());
+    assert!(client.login_info().is_none());
+}
+
+#[test]
+fn api_key_login() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.log_file("api_key_login.txt");
+    let login = client.login_with_api_key("eXaMpLeKeY123==").unwrap();
+    assert!(login.is_success());
+    assert!(!client.sid().is_empty());
+    assert!(!client.uid().is_empty());
+    assert!(!client.api_server_version().is_empty());
+
+    client.logout().unwrap();
+    client.save_log().unwrap();
 }
 
 #[test]
@@ -72,10 +94,9 @@ fn readonly() {
     assert!(client.uid().is_empty());
     println!("empty uid: '{}'", client.uid());
 
-    let res = client.call("add-host", json!({"name": "host1", "ip-address": "1.1.1.1"})).unwrap();
-
-    if res.is_not_success() {
-        println!("code: {}, message: {}", res.data["code"], res.data["message"]);
+    match client.call("add-host", json!({"name": "host1", "ip-address": "1.1.1.1"})) {
+        Err(Error::ReadOnly(command)) => assert_eq!("add-host", command),
+        r => panic!("add-host was not refused: {:?}", r),
     }
 
     client.logout().unwrap();
@@ -106,3 +127,42 @@ fn continuelast() {
     let res = client.call("show-session", json!({"uid": client.uid()})).unwrap();
     println!("changes: {}, locks: {}", res.data["changes"], res.data["locks"]);
 }
+
+#[test]
+fn relogin_after_expired() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.session_timeout(10);
+    client.auto_relogin(true);
+    client.log_file("relogin.txt");
+    client.login("cp_api", "vpn123").unwrap();
+    let first_sid = client.sid().to_string();
+
+    std::thread::sleep(std::time::Duration::from_secs(90));
+
+    let res = client.call("show-host", json!({"name": "host1"})).unwrap();
+    println!("status: {}, message: {}", res.status(), res.data["message"]);
+
+    assert_eq!(1, client.relogin_count());
+    assert_ne!(first_sid, client.sid());
+
+    client.logout().unwrap();
+    client.save_log().unwrap();
+}
+
+// Run on the management server
+#[test]
+fn login_as_root() {
+    let mut client = Client::new("127.0.0.1", 443);
+    client.accept_invalid_certs(true);
+    client.log_file("login_as_root.txt");
+    let login = client.login_as_root().unwrap();
+    assert!(login.is_success());
+    assert!(!client.sid().is_empty());
+
+    let res = client.call("show-host", json!({"name": "host1"})).unwrap();
+    println!("name = {}, ip = {}", res.data["name"], res.data["ipv4-address"]);
+
+    client.logout().unwrap();
+    client.save_log().unwrap();
+}
@@ -1,4 +1,5 @@
 use cp_api::Client;
+use serde_json::json;
 
 #[test]
 fn show_hosts() {
@@ -8,7 +9,7 @@ fn show_hosts() {
 
     println!("Querying all hosts...");
 
-    let hosts = client.query("show-hosts", "standard").unwrap();
+    let hosts = client.query("show-hosts", json!({"details-level": "standard"})).unwrap();
 
     for host in &hosts.objects {
         println!("{} - {}", host["name"], host["ipv4-address"]);
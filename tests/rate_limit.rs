@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cp_api::{Client, Error, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+mod common;
+use common::mock_client;
+
+// A Client that answers show-hosts with pages of 1 host out of 3.
+fn client() -> Client {
+    mock_client(|_command: &str, _headers: &HeaderMap, payload: &serde_json::Value| {
+        let offset = payload["offset"].as_u64().unwrap_or(0);
+        let data = json!({
+            "from": offset + 1,
+            "to": offset + 1,
+            "total": 3,
+            "objects": [{"name": format!("host{}", offset)}],
+        });
+
+        Ok(TransportResponse::json(200, &data))
+    })
+}
+
+#[test]
+fn burst_then_rate() {
+    let mut client = client();
+    client.rate_limit(20.0, 2).unwrap();
+
+    let start = Instant::now();
+    client.call("show-hosts", json!({})).unwrap();
+    client.call("show-hosts", json!({})).unwrap();
+    assert!(start.elapsed() < Duration::from_millis(40));
+
+    for _ in 0..4 {
+        client.call("show-hosts", json!({})).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
+
+#[test]
+fn query_pages() {
+    let mut client = client();
+    client.rate_limit(10.0, 1).unwrap();
+
+    let start = Instant::now();
+    let res = client.query("show-hosts", json!({"limit": 1})).unwrap();
+    assert_eq!(3, res.objects.len());
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
+
+#[test]
+fn no_limit() {
+    let mut client = client();
+    client.rate_limit(1.0, 1).unwrap();
+    client.rate_limit(0.0, 1).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        client.call("show-hosts", json!({})).unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn invalid_rate() {
+    let mut client = client();
+
+    for rate in &[-1.0, f64::NAN, f64::INFINITY] {
+        match client.rate_limit(*rate, 1) {
+            Err(Error::Custom(_)) => (),
+            r => panic!("rate {} was not refused: {:?}", rate, r),
+        }
+    }
+
+    match client.rate_limit(1.0, 0) {
+        Err(Error::Custom(_)) => (),
+        r => panic!("burst 0 was not refused: {:?}", r),
+    }
+}
+
+#[test]
+fn tiny_rate() {
+    let mut client = client();
+    client.rate_limit(1e-300, 1).unwrap();
+
+    client.call("show-hosts", json!({})).unwrap();
+}
+
+#[test]
+fn limit_keepalive() {
+    let keepalives = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&keepalives);
+
+    let mut client = Client::new("192.168.1.10", 443);
+    client.logout_on_drop(false);
+    client.session_timeout(1);
+    client.transport(move |command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+        match command {
+            "login" => Ok(TransportResponse::json(200, &json!({
+                "sid": "sid", "uid": "1", "api-server-version": "1.5", "session-timeout": 1
+            }))),
+            _ => {
+                *counter.lock().unwrap() += 1;
+                Ok(TransportResponse::json(200, &json!({})))
+            },
+        }
+    });
+    client.rate_limit(2.0, 1).unwrap();
+    client.login("user", "pass").unwrap();
+
+    // A keepalive every 100 milliseconds, limited to 2 per second.
+    let keepalive = client.start_keepalive(0.1).unwrap();
+    thread::sleep(Duration::from_millis(1200));
+    client.stop_keepalive();
+
+    assert!(*keepalives.lock().unwrap() <= 3);
+    assert!(keepalive.count() >= 1);
+}
@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex};
+
+use cp_api::{Client, Error, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+// Nothing listens on this port, so any call that is sent fails to connect.
+fn client() -> Client {
+    let mut client = Client::new("127.0.0.1", 1);
+    client.read_only(true);
+    client
+}
+
+#[test]
+fn refuse_mutating_commands() {
+    let mut client = client();
+
+    for command in &["add-host", "set-host", "delete-host", "publish", "install-policy", "discard"] {
+        match client.call(command, json!({})) {
+            Err(Error::ReadOnly(c)) => assert_eq!(*command, c),
+            r => panic!("{} was not refused: {:?}", command, r),
+        }
+    }
+}
+
+#[test]
+fn allow_show_commands() {
+    let mut client = client();
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::ReadOnly(_)) => panic!("show-hosts was refused"),
+        Err(_) => (),
+        Ok(_) => panic!("show-hosts should fail to connect"),
+    }
+}
+
+#[test]
+fn override_classification() {
+    let mut client = client();
+    client.mutating_command("add-host", false);
+    client.mutating_command("show-hosts", true);
+
+    if let Err(Error::ReadOnly(_)) = client.call("add-host", json!({})) {
+        panic!("add-host was refused");
+    }
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::ReadOnly(c)) => assert_eq!("show-hosts", c),
+        r => panic!("show-hosts was not refused: {:?}", r),
+    }
+}
+
+#[test]
+fn not_read_only() {
+    let mut client = Client::new("127.0.0.1", 1);
+
+    if let Err(Error::ReadOnly(_)) = client.call("add-host", json!({})) {
+        panic!("add-host was refused");
+    }
+}
+
+// A read only Client that records the commands sent, and allows set-session
+// so a Session can be created.
+fn session_client(commands: Arc<Mutex<Vec<String>>>) -> Client {
+    let mut client = client();
+    client.mutating_command("set-session", false);
+    client.transport(move |command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+        commands.lock().unwrap().push(command.to_string());
+        Ok(TransportResponse::json(200, &json!({})))
+    });
+    client
+}
+
+#[test]
+fn drop_session_without_discard() {
+    let commands = Arc::new(Mutex::new(Vec::new()));
+    let mut client = session_client(Arc::clone(&commands));
+
+    let session = client.session("read only").unwrap();
+    drop(session);
+
+    assert_eq!(vec!["set-session"], *commands.lock().unwrap());
+
+    // The discard is sent when the Client allows it.
+    client.mutating_command("discard", false);
+    client.session("read only").unwrap();
+
+    assert_eq!(vec!["set-session", "set-session", "discard"], *commands.lock().unwrap());
+}
+
+#[test]
+fn domain_keeps_overrides() {
+    let mut mds = client();
+    mds.mutating_command("add-host", false);
+    mds.transport(|command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+        match command {
+            "login-to-domain" => Ok(TransportResponse::json(200, &json!({
+                "sid": "domain-sid", "api-server-version": "1.5", "session-timeout": 600
+            }))),
+            _ => Ok(TransportResponse::json(200, &json!({}))),
+        }
+    });
+
+    let mut domain = mds.login_to_domain("Domain A").unwrap();
+    domain.logout_on_drop(false);
+
+    assert!(domain.call("add-host", json!({})).unwrap().is_success());
+    match domain.call("set-host", json!({})) {
+        Err(Error::ReadOnly(c)) => assert_eq!("set-host", c),
+        r => panic!("set-host was not refused: {:?}", r),
+    }
+}
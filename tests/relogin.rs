@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+
+use cp_api::{Client, Error, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+// A Client whose first session expires after the login,
+// recording the commands sent with the sid of each one.
+fn expiring_client(commands: Arc<Mutex<Vec<String>>>) -> Client {
+    let mut client = Client::new("192.168.1.10", 443);
+    client.logout_on_drop(false);
+    client.auto_relogin(true);
+
+    let logins = Mutex::new(0);
+    client.transport(move |command: &str, headers: &HeaderMap, _payload: &serde_json::Value| {
+        let sid = headers.get("x-chkp-sid").map(|v| v.to_str().unwrap().to_string());
+        commands.lock().unwrap().push(format!("{} {}", command, sid.unwrap_or_default()));
+
+        if command == "login" {
+            let mut logins = logins.lock().unwrap();
+            *logins += 1;
+            return Ok(TransportResponse::json(200, &json!({
+                "sid": format!("sid{}", *logins), "uid": "1",
+                "api-server-version": "1.5", "session-timeout": 600
+            })));
+        }
+
+        if headers["x-chkp-sid"] == "sid1" {
+            return Ok(TransportResponse::json(400, &json!({
+                "code": "generic_err_wrong_session_id", "message": "Wrong session id"
+            })));
+        }
+
+        Ok(TransportResponse::json(200, &json!({})))
+    });
+
+    client
+}
+
+#[test]
+fn retry_show_after_expired() {
+    let commands = Arc::new(Mutex::new(Vec::new()));
+    let mut client = expiring_client(Arc::clone(&commands));
+    client.login("user", "pass").unwrap();
+
+    assert!(client.call("show-hosts", json!({})).unwrap().is_success());
+    assert_eq!(1, client.relogin_count());
+    assert_eq!(vec!["login ", "show-hosts sid1", "login ", "show-hosts sid2"],
+               *commands.lock().unwrap());
+}
+
+#[test]
+fn publish_after_expired() {
+    let commands = Arc::new(Mutex::new(Vec::new()));
+    let mut client = expiring_client(Arc::clone(&commands));
+    client.login("user", "pass").unwrap();
+
+    match client.call("publish", json!({})) {
+        Err(Error::Custom(msg)) => assert!(msg.contains("unpublished changes were lost")),
+        r => panic!("publish was not refused: {:?}", r),
+    }
+
+    // The Client is logged into the new session, but publish was not sent in it.
+    assert_eq!("sid2", client.sid());
+    assert_eq!(vec!["login ", "publish sid1", "login "], *commands.lock().unwrap());
+}
@@ -0,0 +1,71 @@
+use std::fs;
+
+use cp_api::{Client, Credentials, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+#[test]
+fn save_and_resume() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+    client.logout_on_drop(false);
+    client.save_session("resume_session.json").unwrap();
+    let sid = client.sid().to_string();
+    drop(client);
+
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    let res = client.resume_session("resume_session.json").unwrap();
+    assert!(res.is_success());
+    assert_eq!(sid, client.sid());
+
+    client.logout().unwrap();
+}
+
+#[test]
+fn resume_after_logout() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+    client.save_session("resume_logout.json").unwrap();
+    let sid = client.sid().to_string();
+    client.logout().unwrap();
+
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.credential_provider(Credentials::password("cp_api", "vpn123"));
+    let res = client.resume_session("resume_logout.json").unwrap();
+    assert!(res.is_success());
+    assert_ne!(sid, client.sid());
+
+    client.logout().unwrap();
+}
+
+#[test]
+fn login_when_not_valid() {
+    fs::write("resume_expired.json", r#"{"server": "192.168.1.10", "port": 443, "sid": "old",
+        "uid": "1", "api-server-version": "1.5", "domain": ""}"#).unwrap();
+
+    let mut client = Client::new("192.168.1.10", 443);
+    client.logout_on_drop(false);
+    client.credential_provider(Credentials::password("user", "pass"));
+    client.transport(|command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+        match command {
+            "login" => Ok(TransportResponse::json(200, &json!({
+                "sid": "new", "uid": "2", "api-server-version": "1.5", "session-timeout": 600
+            }))),
+            _ => Ok(TransportResponse::json(400, &json!({
+                "code": "generic_err_wrong_session_id", "message": "Wrong session id"
+            }))),
+        }
+    });
+
+    let res = client.resume_session("resume_expired.json").unwrap();
+    assert!(res.is_success());
+    assert_eq!("new", client.sid());
+    assert!(client.login_info().is_some());
+    assert_eq!(0, client.relogin_count());
+
+    fs::remove_file("resume_expired.json").unwrap();
+}
@@ -0,0 +1,143 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use cp_api::{Client, Error, RetryPolicy, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+mod common;
+use common::mock_client;
+
+fn policy() -> RetryPolicy {
+    RetryPolicy {
+        initial_delay: Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+// A Client whose Transport fails the first `failures` attempts with the `fail` closure.
+fn flaky_client<F>(failures: usize, fail: F) -> (Client, Arc<AtomicUsize>)
+    where F: Fn() -> cp_api::Result<TransportResponse> + Send + Sync + 'static
+{
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&attempts);
+
+    let client = mock_client(move |_command: &str, _headers: &HeaderMap,
+                                   _payload: &serde_json::Value| {
+        if counter.fetch_add(1, Ordering::SeqCst) < failures {
+            return fail();
+        }
+
+        Ok(TransportResponse::json(200, &json!({})))
+    });
+
+    (client, attempts)
+}
+
+fn unavailable() -> cp_api::Result<TransportResponse> {
+    Ok(TransportResponse::json(503, &json!({"code": "generic_err_server_unavailable"})))
+}
+
+#[test]
+fn retry_server_errors() {
+    let (mut client, attempts) = flaky_client(2, unavailable);
+    client.retry_policy(policy());
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+    assert_eq!(3, attempts.load(Ordering::SeqCst));
+}
+
+#[test]
+fn retry_network_errors() {
+    let (mut client, attempts) = flaky_client(1, || {
+        Err(Error::Io(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset")))
+    });
+    client.retry_policy(policy());
+
+    assert!(client.call("show-hosts", json!({})).unwrap().is_success());
+    assert_eq!(2, attempts.load(Ordering::SeqCst));
+}
+
+#[test]
+fn max_attempts() {
+    let (mut client, attempts) = flaky_client(5, unavailable);
+    client.retry_policy(policy());
+
+    assert_eq!(503, client.call("show-hosts", json!({})).unwrap().status());
+    assert_eq!(3, attempts.load(Ordering::SeqCst));
+}
+
+#[test]
+fn only_show_commands_by_default() {
+    let (mut client, attempts) = flaky_client(1, unavailable);
+    client.retry_policy(policy());
+
+    assert_eq!(503, client.call("add-host", json!({})).unwrap().status());
+    assert_eq!(1, attempts.load(Ordering::SeqCst));
+
+    let (mut client, attempts) = flaky_client(1, unavailable);
+    client.retry_policy(RetryPolicy {
+        commands: vec![String::from("add-host")],
+        ..policy()
+    });
+
+    assert!(client.call("add-host", json!({})).unwrap().is_success());
+    assert_eq!(2, attempts.load(Ordering::SeqCst));
+}
+
+#[test]
+fn not_retryable() {
+    let (mut client, attempts) = flaky_client(1, || {
+        Ok(TransportResponse::json(404, &json!({"code": "generic_err_object_not_found"})))
+    });
+    client.retry_policy(policy());
+
+    assert_eq!(404, client.call("show-host", json!({})).unwrap().status());
+    assert_eq!(1, attempts.load(Ordering::SeqCst));
+}
+
+#[test]
+fn backoff() {
+    let policy = RetryPolicy {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(5),
+        jitter: false,
+        ..Default::default()
+    };
+
+    assert_eq!(Duration::from_secs(1), policy.delay(1));
+    assert_eq!(Duration::from_secs(2), policy.delay(2));
+    assert_eq!(Duration::from_secs(4), policy.delay(3));
+    assert_eq!(Duration::from_secs(5), policy.delay(4));
+    assert_eq!(Duration::from_secs(5), policy.delay(40));
+
+    let policy = RetryPolicy { jitter: true, ..policy };
+    for _ in 0..20 {
+        let delay = policy.delay(2);
+        assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+    }
+}
+
+#[test]
+fn retries_in_log() {
+    let file = env::temp_dir().join(format!("cp_api_retry_log_{}", std::process::id()));
+    let (mut client, _) = flaky_client(1, unavailable);
+    client.retry_policy(policy());
+    client.log_file(file.to_str().unwrap());
+
+    client.call("show-hosts", json!({})).unwrap();
+    client.save_log().unwrap();
+
+    let log: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(&file).unwrap())
+        .unwrap();
+    let _ = fs::remove_file(&file);
+
+    assert_eq!(2, log.len());
+    assert_eq!(503, log[0]["Response"]["status"]);
+    assert_eq!(200, log[1]["Response"]["status"]);
+}
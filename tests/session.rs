@@ -0,0 +1,40 @@
+use cp_api::{Client, Error};
+use serde_json::json;
+
+#[test]
+fn session_commit() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+
+    let mut session = client.session("cp_api session test").unwrap();
+    session.call_and_check("add-host", json!({"name": "session_host", "ip-address": "1.1.1.2"})).unwrap();
+    let publish = session.commit().unwrap();
+    assert!(publish.is_success());
+
+    let res = client.call("show-host", json!({"name": "session_host"})).unwrap();
+    assert!(res.is_success());
+
+    client.logout().unwrap();
+}
+
+#[test]
+fn session_discard_on_error() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+
+    let result: Result<(), Error> = (|| {
+        let mut session = client.session("cp_api session test")?;
+        session.call_and_check("add-host", json!({"name": "discarded_host", "ip-address": "1.1.1.3"}))?;
+        session.call_and_check("add-host", json!({"name": "bad_host", "ip-address": "not an ip"}))?;
+        session.commit()?;
+        Ok(())
+    })();
+    assert!(result.is_err());
+
+    let res = client.call("show-host", json!({"name": "discarded_host"})).unwrap();
+    assert!(res.is_not_success());
+
+    client.logout().unwrap();
+}
@@ -0,0 +1,42 @@
+use cp_api::{CleanupPolicy, Client};
+use std::time::Duration;
+
+#[test]
+fn show_sessions() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+
+    let sessions = client.show_sessions().unwrap();
+    assert!(sessions.iter().any(|s| s.uid == client.uid()));
+
+    for session in &sessions {
+        println!("{} {} {} - changes: {}, locks: {}",
+                 session.uid, session.user_name, session.application, session.changes, session.locks);
+    }
+
+    client.logout().unwrap();
+}
+
+#[test]
+fn cleanup_dry_run() {
+    let mut client = Client::new("172.25.199.80", 443);
+    client.accept_invalid_certs(true);
+    client.login("cp_api", "vpn123").unwrap();
+
+    let policy = CleanupPolicy {
+        min_age: Some(Duration::from_secs(60 * 60)),
+        ..Default::default()
+    };
+
+    let report = client.cleanup_sessions(&policy).unwrap();
+    assert!(report.dry_run);
+    assert!(report.failed.is_empty());
+    assert!(report.skipped.iter().any(|(s, _)| s.uid == client.uid()));
+
+    for (session, reason) in &report.skipped {
+        println!("skipped {}: {}", session.uid, reason);
+    }
+
+    client.logout().unwrap();
+}
@@ -0,0 +1,135 @@
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use cp_api::{Client, Error, RetryPolicy, TransportResponse};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+use serde_json::json;
+
+mod common;
+use common::{mock_client, read_request};
+
+fn gzip(data: &serde_json::Value) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data.to_string().as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+// A page of show-hosts with 2 hosts per page out of 5.
+fn page(payload: &serde_json::Value) -> serde_json::Value {
+    let offset = payload["offset"].as_u64().unwrap();
+    let to = (offset + 2).min(5);
+    let objects: Vec<_> = (offset..to).map(|i| json!({"name": format!("host{}", i)})).collect();
+
+    json!({"from": offset + 1, "to": to, "total": 5, "objects": objects})
+}
+
+fn client(compressed: bool) -> Client {
+    mock_client(move |_command: &str, _headers: &HeaderMap, payload: &serde_json::Value| {
+        let data = page(payload);
+        if !compressed {
+            return Ok(TransportResponse::json(200, &data));
+        }
+
+        let mut res = TransportResponse::json(200, &data);
+        res.headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        res.body = gzip(&data);
+        Ok(res)
+    })
+}
+
+#[test]
+fn query_each() {
+    let mut names = Vec::new();
+    let res = client(false).query_each("show-hosts", json!({"limit": 2}), |host| {
+        names.push(host["name"].as_str().unwrap().to_string());
+        Ok(())
+    }).unwrap();
+
+    assert!(res.is_success());
+    assert_eq!(vec!["host0", "host1", "host2", "host3", "host4"], names);
+    assert_eq!(5, res.data["total"]);
+    assert!(res.data.get("objects").is_none());
+    assert!(res.objects.is_empty());
+}
+
+#[test]
+fn gzip_pages() {
+    let mut count = 0;
+    client(true).query_each("show-hosts", json!({"limit": 2}), |_| {
+        count += 1;
+        Ok(())
+    }).unwrap();
+    assert_eq!(5, count);
+
+    let hosts = client(true).query("show-hosts", json!({"limit": 2})).unwrap();
+    assert_eq!(5, hosts.objects.len());
+}
+
+#[test]
+fn stop_from_callback() {
+    let mut client = client(false);
+    client.retry_policy(RetryPolicy::default());
+
+    // An interrupted error would be retried if it came from the Transport.
+    let mut count = 0;
+    let res = client.query_each("show-hosts", json!({"limit": 2}), |_| {
+        count += 1;
+        if count == 3 {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::Interrupted, "enough hosts")));
+        }
+        Ok(())
+    });
+
+    match res {
+        Err(Error::Io(e)) => {
+            assert_eq!(io::ErrorKind::Interrupted, e.kind());
+            assert_eq!("enough hosts", e.to_string());
+        },
+        r => panic!("the error of the callback was not returned: {:?}", r),
+    }
+    assert_eq!(3, count);
+}
+
+#[test]
+fn error_page() {
+    let mut client = mock_client(|_command: &str, _headers: &HeaderMap,
+                                  _payload: &serde_json::Value| {
+        let data = json!({"code": "generic_err_invalid_parameter", "message": "bad limit"});
+        Ok(TransportResponse::json(400, &data))
+    });
+
+    let res = client.query_each("show-hosts", json!({"limit": 9000}), |_| Ok(()));
+    assert!(res.is_err());
+}
+
+#[test]
+fn reqwest_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream);
+
+        let body = gzip(&page(&json!({"offset": 3})));
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                            Content-Encoding: gzip\r\nContent-Length: {}\r\n\
+                            Connection: close\r\n\r\n", body.len());
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(&body).unwrap();
+    });
+
+    let mut client = Client::from_url(&format!("http://127.0.0.1:{}/web_api", port)).unwrap();
+
+    let mut names = Vec::new();
+    let res = client.query_each("show-hosts", json!({"offset": 3}), |host| {
+        names.push(host["name"].as_str().unwrap().to_string());
+        Ok(())
+    }).unwrap();
+
+    assert!(res.is_success());
+    assert_eq!(vec!["host3", "host4"], names);
+}
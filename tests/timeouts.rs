@@ -1,4 +1,14 @@
+use std::time::{Duration, Instant};
+
 use cp_api::Client;
+use serde_json::json;
+
+mod common;
+use common::{pinned_client, start_slow_server, start_trickling_server};
+
+fn slow_client(delay: Duration) -> Client {
+    pinned_client(start_slow_server(delay))
+}
 
 #[test]
 fn connect_timeout() {
@@ -30,3 +40,47 @@ fn session_timeout_test() {
     assert_eq!(800, res.data["session-timeout"]);
     client.logout().unwrap();
 }
+
+#[test]
+fn request_timeout() {
+    let mut client = slow_client(Duration::from_secs(3));
+    client.connect_timeout(1);
+    client.request_timeout(1);
+
+    let start = Instant::now();
+    assert!(client.call("show-hosts", json!({})).is_err());
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn request_timeout_defaults_to_connect_timeout() {
+    let mut client = slow_client(Duration::from_secs(3));
+    client.connect_timeout(1);
+
+    let start = Instant::now();
+    assert!(client.call("show-hosts", json!({})).is_err());
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn request_timeout_while_reading() {
+    let mut client = pinned_client(start_trickling_server(Duration::from_millis(100)));
+    client.request_timeout(1);
+
+    // Each byte comes before a read times out, but the whole body takes 10 seconds.
+    let start = Instant::now();
+    assert!(client.call("show-hosts", json!({})).is_err());
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
+
+#[test]
+fn command_timeout() {
+    let mut client = slow_client(Duration::from_secs(2));
+    client.request_timeout(1);
+    client.command_timeout("publish", 5);
+
+    let res = client.call("publish", json!({})).unwrap();
+    assert!(res.is_success());
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
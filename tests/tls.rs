@@ -0,0 +1,167 @@
+use cp_api::{Client, Error};
+use serde_json::json;
+
+mod common;
+use common::{pinned_client, start_server, start_server_answering, start_switching_server,
+             SERVER_FINGERPRINT};
+
+#[test]
+fn server_fingerprint() {
+    let port = start_server();
+    let client = Client::new("127.0.0.1", port);
+
+    assert_eq!(SERVER_FINGERPRINT, client.server_fingerprint().unwrap());
+}
+
+#[test]
+fn pinned_fingerprint() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate_fingerprint(&SERVER_FINGERPRINT.replace(':', "").to_lowercase());
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+}
+
+#[test]
+fn fingerprint_mismatch() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate_fingerprint(&"AB:".repeat(32)[..95]);
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::FingerprintMismatch(expected, observed)) => {
+            assert_eq!("AB:".repeat(32)[..95], expected);
+            assert_eq!(SERVER_FINGERPRINT, observed);
+        },
+        r => panic!("the fingerprint mismatch was not detected: {:?}", r),
+    }
+}
+
+#[test]
+fn certificate_changed_after_fingerprint() {
+    let port = start_switching_server();
+    let mut client = pinned_client(port);
+
+    // The first connection presents the pinned certificate, the API connection does not.
+    assert_eq!(SERVER_FINGERPRINT, client.server_fingerprint().unwrap());
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::FingerprintMismatch(expected, observed)) => {
+            assert_eq!(SERVER_FINGERPRINT, expected);
+            assert_ne!(SERVER_FINGERPRINT, observed);
+        },
+        r => panic!("the changed certificate was not detected: {:?}", r),
+    }
+}
+
+#[test]
+fn certificate_changed_between_calls() {
+    let port = start_switching_server();
+    let mut client = pinned_client(port);
+
+    assert!(client.call("show-hosts", json!({})).unwrap().is_success());
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::FingerprintMismatch(..)) => (),
+        r => panic!("the changed certificate was not detected: {:?}", r),
+    }
+}
+
+#[test]
+fn pinned_chunked_response() {
+    let port = start_server_answering(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                        Transfer-Encoding: chunked\r\n\r\n\
+                                        a\r\n{\"total\": \r\n2;ext=1\r\n12\r\n1\r\n}\r\n0\r\n\r\n");
+    let mut client = pinned_client(port);
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert_eq!(json!({"total": 12}), res.data);
+}
+
+#[test]
+fn pinned_invalid_chunk_end() {
+    let port = start_server_answering(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                        Transfer-Encoding: chunked\r\n\r\n\
+                                        2\r\n{}XX0\r\n\r\n");
+    let mut client = pinned_client(port);
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
+
+#[test]
+fn pinned_header_not_ascii() {
+    let port = start_server_answering(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                        X-Server-Name: caf\xc3\xa9\r\n\
+                                        Content-Length: 2\r\nConnection: close\r\n\r\n{}");
+    let pinned = pinned_client(port);
+    let mut unpinned = Client::new("127.0.0.1", port);
+    unpinned.accept_invalid_certs(true);
+
+    // The header is read the same as with reqwest, and only the Response rejects it.
+    for client in &mut [pinned, unpinned] {
+        match client.call("show-hosts", json!({})) {
+            Err(Error::HeaderToStr(_)) => (),
+            r => panic!("the header was not read: {:?}", r),
+        }
+    }
+}
+
+#[test]
+fn pinned_invalid_content_length() {
+    let port = start_server_answering(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                        Content-Length: two\r\nConnection: close\r\n\r\n{}");
+    let mut client = pinned_client(port);
+
+    match client.call("show-hosts", json!({})) {
+        Err(Error::Io(e)) => assert_eq!(std::io::ErrorKind::InvalidData, e.kind()),
+        r => panic!("the invalid Content-Length was not detected: {:?}", r),
+    }
+}
+
+#[test]
+fn invalid_fingerprint() {
+    let mut client = Client::new("127.0.0.1", 1);
+    client.certificate_fingerprint("not a fingerprint");
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
+
+#[test]
+fn pem_certificate() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate("tests/data/server.pem");
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+}
+
+#[test]
+fn pem_bundle() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate("tests/data/bundle.pem");
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+}
+
+#[test]
+fn der_certificate() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate("tests/data/server.der");
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_success());
+}
+
+#[test]
+fn untrusted_certificate() {
+    let port = start_server();
+    let mut client = Client::new("127.0.0.1", port);
+    client.certificate("tests/data/ca.pem");
+
+    assert!(client.call("show-hosts", json!({})).is_err());
+}
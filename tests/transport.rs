@@ -0,0 +1,127 @@
+use std::sync::{Arc, Mutex};
+
+use cp_api::{Client, Transport, TransportResponse, Result};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+mod common;
+use common::mock_client;
+
+// A Transport that answers from memory and records the commands sent.
+#[derive(Clone, Default)]
+struct Mock {
+    sent: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+}
+
+impl Transport for Mock {
+    fn send(
+        &self,
+        command: &str,
+        headers: &HeaderMap,
+        payload: &serde_json::Value
+        ) -> Result<TransportResponse>
+    {
+        self.sent.lock().unwrap().push((command.to_string(), payload.clone()));
+
+        if command != "login" && headers.get("x-chkp-sid").is_none() {
+            let data = json!({"code": "generic_err_wrong_session_id"});
+            return Ok(TransportResponse::json(401, &data));
+        }
+
+        let data = match command {
+            "login" => json!({
+                "sid": "mock-sid",
+                "uid": "mock-uid",
+                "api-server-version": "1.5",
+                "session-timeout": 600,
+            }),
+            "show-hosts" => {
+                let offset = payload["offset"].as_u64().unwrap();
+                let to = (offset + 2).min(3);
+                let objects: Vec<_> = (offset..to).map(|i| json!({"name": format!("host{}", i)}))
+                    .collect();
+                json!({"from": offset + 1, "to": to, "total": 3, "objects": objects})
+            },
+            "publish" => json!({"task-id": "task1"}),
+            "show-task" => json!({"tasks": [{"status": "succeeded", "progress-percentage": 100}]}),
+            "logout" => json!({"message": "OK"}),
+            _ => {
+                let data = json!({"code": "generic_err_command_not_found"});
+                return Ok(TransportResponse::json(404, &data));
+            }
+        };
+
+        Ok(TransportResponse::json(200, &data))
+    }
+}
+
+fn client(mock: &Mock) -> Client {
+    let mut client = Client::new("mock", 443);
+    client.transport(mock.clone());
+    client.logout_on_drop(false);
+    client
+}
+
+#[test]
+fn login_and_logout() {
+    let mock = Mock::default();
+    let mut client = client(&mock);
+
+    let login = client.login("user", "pass").unwrap();
+    assert!(login.is_success());
+    assert_eq!("mock-sid", client.sid());
+    assert_eq!("mock-uid", client.uid());
+    assert_eq!("https://mock:443/web_api/login", login.url());
+
+    client.logout().unwrap();
+    assert!(client.sid().is_empty());
+
+    let sent = mock.sent.lock().unwrap();
+    assert_eq!("login", sent[0].0);
+    assert_eq!("user", sent[0].1["user"]);
+    assert_eq!("logout", sent[1].0);
+}
+
+#[test]
+fn query_pages() {
+    let mock = Mock::default();
+    let mut client = client(&mock);
+    client.login("user", "pass").unwrap();
+
+    let hosts = client.query_and_check("show-hosts", json!({"limit": 2})).unwrap();
+    let names: Vec<_> = hosts.objects.iter().map(|h| h["name"].as_str().unwrap()).collect();
+    assert_eq!(vec!["host0", "host1", "host2"], names);
+
+    let offsets: Vec<_> = mock.sent.lock().unwrap().iter()
+        .filter(|(c, _)| c == "show-hosts")
+        .map(|(_, p)| p["offset"].as_u64().unwrap())
+        .collect();
+    assert_eq!(vec![0, 2], offsets);
+}
+
+#[test]
+fn wait_for_task() {
+    let mock = Mock::default();
+    let mut client = client(&mock);
+    client.login("user", "pass").unwrap();
+
+    let publish = client.call_and_check("publish", json!({})).unwrap();
+    assert_eq!("succeeded", publish.data["tasks"][0]["status"]);
+
+    let sent = mock.sent.lock().unwrap();
+    assert_eq!("show-task", sent.last().unwrap().0);
+    assert_eq!("task1", sent.last().unwrap().1["task-id"]);
+}
+
+#[test]
+fn closure_transport() {
+    let mut client = mock_client(|_command: &str, _headers: &HeaderMap,
+                                  _payload: &serde_json::Value| {
+        Ok(TransportResponse::json(500, &json!({"code": "generic_error", "message": "down"})))
+    });
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert!(res.is_server_error());
+    assert_eq!("down", res.data["message"]);
+    assert!(client.call_and_check("show-hosts", json!({})).is_err());
+}
@@ -0,0 +1,89 @@
+use cp_api::{ApiVersion, Client, TransportResponse};
+use reqwest::header::HeaderMap;
+use serde_json::json;
+
+mod common;
+use common::mock_client;
+
+#[test]
+fn parse_versions() {
+    assert_eq!(ApiVersion::new(1, 5), "1.5".parse().unwrap());
+    assert_eq!(ApiVersion::new(1, 5), "v1.5".parse().unwrap());
+    assert_eq!(ApiVersion { major: 1, minor: 6, patch: 1 }, "1.6.1".parse().unwrap());
+    assert_eq!(ApiVersion::new(2, 0), "2".parse().unwrap());
+
+    assert!("".parse::<ApiVersion>().is_err());
+    assert!("1.x".parse::<ApiVersion>().is_err());
+    assert!("1.2.3.4".parse::<ApiVersion>().is_err());
+}
+
+#[test]
+fn compare_versions() {
+    let v1_5: ApiVersion = "1.5".parse().unwrap();
+    let v1_6_1: ApiVersion = "1.6.1".parse().unwrap();
+    let v1_10: ApiVersion = "1.10".parse().unwrap();
+
+    assert!(v1_5 < v1_6_1);
+    assert!(v1_6_1 < v1_10);
+    assert_eq!("1.6.1", v1_6_1.to_string());
+    assert_eq!("1.10", v1_10.to_string());
+}
+
+fn versions_client() -> Client {
+    mock_client(|command: &str, _headers: &HeaderMap, _payload: &serde_json::Value| {
+        let data = match command {
+            "show-api-versions" => json!({
+                "current-version": "1.8",
+                "supported-versions": ["1", "1.1", "1.5", "1.6", "1.6.1", "1.7", "1.8"],
+            }),
+            _ => json!({}),
+        };
+
+        Ok(TransportResponse::json(200, &data))
+    })
+}
+
+#[test]
+fn pinned_version() {
+    let mut client = versions_client();
+    client.api_version(ApiVersion::new(1, 5));
+    assert_eq!("https://192.168.1.10:443/web_api/v1.5", client.base_url());
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert_eq!("https://192.168.1.10:443/web_api/v1.5/show-hosts", res.url());
+}
+
+#[test]
+fn negotiate_version() {
+    let mut client = versions_client();
+
+    let version = client.negotiate_api_version(ApiVersion::new(1, 5), ApiVersion::new(1, 6))
+        .unwrap();
+    assert_eq!(ApiVersion::new(1, 6), version);
+    assert_eq!("https://192.168.1.10:443/web_api/v1.6", client.base_url());
+
+    let version = client.negotiate_api_version(ApiVersion::new(1, 7), ApiVersion::new(2, 0))
+        .unwrap();
+    assert_eq!(ApiVersion::new(1, 8), version);
+}
+
+#[test]
+fn negotiate_major_version() {
+    let mut client = versions_client();
+
+    let version = client.negotiate_api_version(ApiVersion::new(1, 0), ApiVersion::new(1, 0))
+        .unwrap();
+    assert_eq!(ApiVersion::new(1, 0), version);
+    assert_eq!("https://192.168.1.10:443/web_api/v1", client.base_url());
+
+    let res = client.call("show-hosts", json!({})).unwrap();
+    assert_eq!("https://192.168.1.10:443/web_api/v1/show-hosts", res.url());
+}
+
+#[test]
+fn no_compatible_version() {
+    let mut client = versions_client();
+
+    assert!(client.negotiate_api_version(ApiVersion::new(2, 0), ApiVersion::new(2, 5)).is_err());
+    assert_eq!("https://192.168.1.10:443/web_api", client.base_url());
+}